@@ -0,0 +1,15 @@
+-- Rows stored before granularities were tracked are all daily.
+ALTER TABLE depth_price_history ADD COLUMN interval TEXT NOT NULL DEFAULT 'day';
+ALTER TABLE swaps_history ADD COLUMN interval TEXT NOT NULL DEFAULT 'day';
+ALTER TABLE earnings_history ADD COLUMN interval TEXT NOT NULL DEFAULT 'day';
+ALTER TABLE rune_pool_history ADD COLUMN interval TEXT NOT NULL DEFAULT 'day';
+
+-- The same time range may now be stored once per granularity.
+ALTER TABLE depth_price_history DROP CONSTRAINT depth_price_history_pool_start_time_end_time_key;
+ALTER TABLE depth_price_history ADD CONSTRAINT depth_price_history_pool_interval_start_time_end_time_key UNIQUE (pool, interval, start_time, end_time);
+ALTER TABLE swaps_history DROP CONSTRAINT swaps_history_pool_start_time_end_time_key;
+ALTER TABLE swaps_history ADD CONSTRAINT swaps_history_pool_interval_start_time_end_time_key UNIQUE (pool, interval, start_time, end_time);
+ALTER TABLE earnings_history DROP CONSTRAINT earnings_history_start_time_end_time_key;
+ALTER TABLE earnings_history ADD CONSTRAINT earnings_history_interval_start_time_end_time_key UNIQUE (interval, start_time, end_time);
+ALTER TABLE rune_pool_history DROP CONSTRAINT rune_pool_history_start_time_end_time_key;
+ALTER TABLE rune_pool_history ADD CONSTRAINT rune_pool_history_interval_start_time_end_time_key UNIQUE (interval, start_time, end_time);
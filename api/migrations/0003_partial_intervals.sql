@@ -0,0 +1,6 @@
+-- Whether the interval had not closed yet when it was last fetched; rows stored before this were
+-- all treated as final.
+ALTER TABLE depth_price_history ADD COLUMN is_partial BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE swaps_history ADD COLUMN is_partial BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE earnings_history ADD COLUMN is_partial BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE rune_pool_history ADD COLUMN is_partial BOOLEAN NOT NULL DEFAULT FALSE;
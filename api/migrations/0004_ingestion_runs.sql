@@ -0,0 +1,15 @@
+-- One row per fetch of a dataset, pool and interval, for /api/ingestion/status.
+CREATE TABLE ingestion_runs (
+  id SERIAL PRIMARY KEY,
+  dataset TEXT NOT NULL,
+  pool TEXT,
+  interval TEXT NOT NULL,
+  started_at TIMESTAMPTZ NOT NULL,
+  finished_at TIMESTAMPTZ,
+  rows_inserted BIGINT NOT NULL DEFAULT 0,
+  rows_updated BIGINT NOT NULL DEFAULT 0,
+  http_attempts INTEGER NOT NULL DEFAULT 0,
+  error TEXT
+);
+
+CREATE INDEX ingestion_runs_dataset_idx ON ingestion_runs (dataset, interval, started_at);
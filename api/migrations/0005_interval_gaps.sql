@@ -0,0 +1,15 @@
+-- Missing intervals found between stored ones, and how repairing them went.
+CREATE TABLE interval_gaps (
+  id SERIAL PRIMARY KEY,
+  dataset TEXT NOT NULL,
+  pool TEXT,
+  interval TEXT NOT NULL,
+  gap_start TIMESTAMPTZ NOT NULL,
+  gap_end TIMESTAMPTZ NOT NULL,
+  detected_at TIMESTAMPTZ NOT NULL,
+  last_attempt_at TIMESTAMPTZ NOT NULL,
+  repair_attempts INTEGER NOT NULL DEFAULT 0,
+  resolved_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX interval_gaps_key ON interval_gaps (dataset, (COALESCE(pool, '')), interval, gap_start);
@@ -0,0 +1,190 @@
+use deadpool_postgres::Pool;
+use log::info;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+// Schema changes, embedded in the binary and applied in version order. Migrations are
+// forward-only: once released, a file must never be edited; change the schema with a new one.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "initial_schema", sql: include_str!("../../migrations/0001_initial_schema.sql") },
+    Migration { version: 2, name: "interval_granularity", sql: include_str!("../../migrations/0002_interval_granularity.sql") },
+    Migration { version: 3, name: "partial_intervals", sql: include_str!("../../migrations/0003_partial_intervals.sql") },
+    Migration { version: 4, name: "ingestion_runs", sql: include_str!("../../migrations/0004_ingestion_runs.sql") },
+    Migration { version: 5, name: "interval_gaps", sql: include_str!("../../migrations/0005_interval_gaps.sql") },
+];
+
+// Advisory lock key ("migr" in ASCII) that keeps two instances from migrating at once.
+const MIGRATION_LOCK: i64 = 0x6d69_6772;
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("migration {version} ({name}) was changed after it was applied: checksum {applied} in schema_migrations, {embedded} embedded")]
+    ChecksumMismatch { version: i32, name: String, applied: String, embedded: String },
+    #[error("database has migration {0}, which this binary does not know; refusing to run an older build against a newer schema")]
+    Unknown(i32),
+}
+
+// A row of schema_migrations.
+pub struct Applied {
+    pub version: i32,
+    pub checksum: String,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.sql.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+// The migrations still to run, after checking that everything already applied matches what is
+// embedded.
+pub fn pending<'a>(migrations: &'a [Migration], applied: &[Applied]) -> Result<Vec<&'a Migration>, MigrationError> {
+    for done in applied {
+        let migration = migrations.iter().find(|m| m.version == done.version).ok_or(MigrationError::Unknown(done.version))?;
+        let embedded = migration.checksum();
+        if embedded != done.checksum {
+            return Err(MigrationError::ChecksumMismatch {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied: done.checksum.clone(),
+                embedded,
+            });
+        }
+    }
+    let mut pending: Vec<&Migration> = migrations.iter().filter(|m| applied.iter().all(|a| a.version != m.version)).collect();
+    pending.sort_by_key(|m| m.version);
+    Ok(pending)
+}
+
+// Applies every pending migration, each in its own transaction together with its
+// schema_migrations row. With `dry_run` it only reports what would run. Returns the versions
+// that were (or would be) applied.
+pub async fn migrate(pool: &Pool, dry_run: bool) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+    let mut client = pool.get().await?;
+    client.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+           version INTEGER PRIMARY KEY,
+           name TEXT NOT NULL,
+           checksum TEXT NOT NULL,
+           applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+         )",
+    ).await?;
+    client.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK]).await?;
+    let result = apply_pending(&mut client, dry_run).await;
+    client.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK]).await?;
+    result
+}
+
+async fn apply_pending(client: &mut deadpool_postgres::Client, dry_run: bool) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+    let applied: Vec<Applied> = client.query("SELECT version, checksum FROM schema_migrations", &[]).await?
+        .iter()
+        .map(|row| Applied { version: row.get("version"), checksum: row.get("checksum") })
+        .collect();
+    let pending = pending(MIGRATIONS, &applied)?;
+    // A database created by hand from the original schema.sql already has the tables of migration
+    // 1, which is then recorded without being run so the later migrations upgrade them in place.
+    let adopt = applied.is_empty()
+        && client.query_one("SELECT to_regclass('depth_price_history') IS NOT NULL", &[]).await?.get::<_, bool>(0);
+    for migration in &pending {
+        let adopted = adopt && migration.version == 1;
+        if dry_run {
+            info!("Would {} migration {} ({})", if adopted { "record existing schema as" } else { "apply" }, migration.version, migration.name);
+            continue;
+        }
+        let tx = client.transaction().await?;
+        if adopted {
+            info!("Recording existing schema as migration {} ({})", migration.version, migration.name);
+        } else {
+            info!("Applying migration {} ({})", migration.version, migration.name);
+            tx.batch_execute(migration.sql).await?;
+        }
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, checksum) VALUES ($1, $2, $3)",
+            &[&migration.version, &migration.name, &migration.checksum()],
+        ).await?;
+        tx.commit().await?;
+    }
+    Ok(pending.iter().map(|m| m.version).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration { version: 1, name: "one", sql: "SELECT 1" },
+        Migration { version: 2, name: "two", sql: "SELECT 2" },
+    ];
+
+    #[test]
+    fn plans_only_unapplied_migrations() {
+        let applied = [Applied { version: 1, checksum: TEST_MIGRATIONS[0].checksum() }];
+        let pending = pending(TEST_MIGRATIONS, &applied).unwrap();
+        assert_eq!(pending.iter().map(|m| m.version).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn rejects_edited_or_unknown_migrations() {
+        let edited = [Applied { version: 1, checksum: "0".repeat(64) }];
+        assert!(matches!(pending(TEST_MIGRATIONS, &edited), Err(MigrationError::ChecksumMismatch { version: 1, .. })));
+
+        let newer = [Applied { version: 3, checksum: String::new() }];
+        assert!(matches!(pending(TEST_MIGRATIONS, &newer), Err(MigrationError::Unknown(3))));
+    }
+
+    // A pool whose connections only see `schema`, standing in for a separate database.
+    fn schema_pool(schema: &str) -> deadpool_postgres::Pool {
+        use deadpool_postgres::{Config as PgConfig, Runtime};
+
+        let mut pg_config = PgConfig::new();
+        pg_config.dbname = Some("api".to_string());
+        pg_config.host = Some("localhost".to_string());
+        pg_config.user = Some("postgres".to_string());
+        pg_config.password = Some("Bhakwaas@csd37".to_string());
+        pg_config.options = Some(format!("-c search_path={}", schema));
+        pg_config.create_pool(Some(Runtime::Tokio1), tokio_postgres::NoTls).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn upgrades_a_hand_created_baseline_schema() {
+        test_pool().get().await.unwrap()
+            .batch_execute("DROP SCHEMA IF EXISTS baseline_upgrade CASCADE; CREATE SCHEMA baseline_upgrade")
+            .await.unwrap();
+        let pool = schema_pool("baseline_upgrade");
+        let client = pool.get().await.unwrap();
+        client.batch_execute(MIGRATIONS[0].sql).await.unwrap();
+
+        let applied = migrate(&pool, false).await.unwrap();
+
+        assert_eq!(applied, MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>());
+        let columns: Vec<String> = client.query(
+            "SELECT column_name::TEXT FROM information_schema.columns WHERE table_schema = 'baseline_upgrade' AND table_name = 'rune_pool_history'", &[],
+        ).await.unwrap().iter().map(|row| row.get(0)).collect();
+        assert!(columns.iter().any(|c| c == "interval") && columns.iter().any(|c| c == "is_partial"), "{:?}", columns);
+        // The same range once per interval, which the baseline key refused.
+        client.batch_execute(
+            "INSERT INTO rune_pool_history (interval, start_time, end_time, count, units)
+             VALUES ('day', '2020-01-01', '2020-01-02', 1, 1), ('hour', '2020-01-01', '2020-01-02', 1, 1)
+             ON CONFLICT (interval, start_time, end_time) DO NOTHING",
+        ).await.unwrap();
+        assert_eq!(client.query_one("SELECT COUNT(*) FROM rune_pool_history", &[]).await.unwrap().get::<_, i64>(0), 2);
+        for table in ["ingestion_runs", "interval_gaps"] {
+            assert!(client.query_one("SELECT to_regclass($1) IS NOT NULL", &[&table]).await.unwrap().get::<_, bool>(0), "{}", table);
+        }
+        client.batch_execute("DROP SCHEMA baseline_upgrade CASCADE").await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn embedded_migrations_are_applied_once() {
+        let pool = test_pool();
+        migrate(&pool, false).await.unwrap();
+        assert_eq!(migrate(&pool, true).await.unwrap(), Vec::<i32>::new());
+    }
+}
@@ -1,6 +1,27 @@
 use deadpool_postgres::Pool;
-use crate::models::{QueryParams, DepthPrice, Swap, Earnings, RunePool, PoolActivity};
+use crate::models::{QueryParams, AggregateParams, Aggregate, CandleParams, Candle, PoolYield, YieldWindow, LpReturn, LpReturnParams, Interval, Page, DepthPrice, Swap, Earnings, PoolEarning, PoolEarningHistory, RunePool, PoolActivity, IngestionStatus, IntervalGap};
 use tokio_postgres::Row;
+use chrono::{DateTime, Utc};
+use query::{aggregate_select, bucket_columns, check_includes, check_no_field_filters, QueryBuilder, QueryError};
+
+pub mod migrations;
+pub mod query;
+
+// Columns `sort_by` may name, per endpoint. The numeric ones can also be filtered with
+// `column[op]=value`.
+const DEPTH_COLUMNS: &[&str] = &["id", "pool", "interval", "start_time", "end_time", "is_partial", "asset_depth", "rune_depth", "asset_price", "asset_price_usd", "liquidity_units", "members_count", "synth_units", "synth_supply", "units", "luvi"];
+const SWAP_COLUMNS: &[&str] = &["id", "pool", "interval", "start_time", "end_time", "is_partial", "to_asset_count", "to_rune_count", "to_trade_count", "from_trade_count", "synth_mint_count", "synth_redeem_count", "total_count", "to_asset_volume", "to_rune_volume", "to_trade_volume", "from_trade_volume", "synth_mint_volume", "synth_redeem_volume", "total_volume", "to_asset_volume_usd", "to_rune_volume_usd", "to_trade_volume_usd", "from_trade_volume_usd", "synth_mint_volume_usd", "synth_redeem_volume_usd", "total_volume_usd", "to_asset_fees", "to_rune_fees", "to_trade_fees", "from_trade_fees", "synth_mint_fees", "synth_redeem_fees", "total_fees", "to_asset_average_slip", "to_rune_average_slip", "to_trade_average_slip", "from_trade_average_slip", "synth_mint_average_slip", "synth_redeem_average_slip", "average_slip", "rune_price_usd"];
+const EARNINGS_COLUMNS: &[&str] = &["id", "interval", "start_time", "end_time", "is_partial", "liquidity_fees", "block_rewards", "earnings", "bonding_earnings", "liquidity_earnings", "avg_node_count", "rune_price_usd"];
+const RUNEPOOL_COLUMNS: &[&str] = &["id", "interval", "start_time", "end_time", "is_partial", "count", "units"];
+// The history tables behind each `/api/{dataset}` endpoint, with their columns.
+const HISTORIES: &[(&str, &str, &[&str])] = &[
+    ("depth-history", "depth_price_history", DEPTH_COLUMNS),
+    ("swaps-history", "swaps_history", SWAP_COLUMNS),
+    ("earnings-history", "earnings_history", EARNINGS_COLUMNS),
+    ("runepool-history", "rune_pool_history", RUNEPOOL_COLUMNS),
+];
+const POOL_EARNINGS_COLUMNS: &[&str] = &["id", "earnings_history_id", "pool", "interval", "start_time", "end_time", "is_partial", "asset_liquidity_fees", "rune_liquidity_fees", "total_liquidity_fees_rune", "saver_earning", "rewards", "earnings"];
+const POOL_ACTIVITY_COLUMNS: &[&str] = &["pool", "interval", "is_partial", "asset_depth", "rune_depth", "asset_price", "to_asset_volume", "total_fees", "total_volume_usd", "start_time", "end_time"];
 
 #[derive(Clone)]
 pub struct Database {
@@ -12,119 +33,687 @@ impl Database {
         Self { pool }
     }
 
-    pub async fn find_depths(&self, params: &QueryParams) -> Result<Vec<DepthPrice>, Box<dyn std::error::Error>> {
-        self.find_records("depth_price_history", params).await
+    pub async fn find_depths(&self, params: &QueryParams) -> Result<Page<DepthPrice>, Box<dyn std::error::Error>> {
+        self.find_records("depth_price_history", DEPTH_COLUMNS, params).await
     }
 
-    pub async fn find_swaps(&self, params: &QueryParams) -> Result<Vec<Swap>, Box<dyn std::error::Error>> {
-        self.find_records("swaps_history", params).await
+    pub async fn find_swaps(&self, params: &QueryParams) -> Result<Page<Swap>, Box<dyn std::error::Error>> {
+        self.find_records("swaps_history", SWAP_COLUMNS, params).await
     }
 
-    pub async fn find_earnings(&self, params: &QueryParams) -> Result<Vec<Earnings>, Box<dyn std::error::Error>> {
-        self.find_records("earnings_history", params).await
+    // With `include=pools`, each interval carries its pool_earnings rows, narrowed by `pool`/`pools`,
+    // aggregated in the same query.
+    pub async fn find_earnings(&self, params: &QueryParams) -> Result<Page<Earnings>, Box<dyn std::error::Error>> {
+        if !params.includes("pools") {
+            return self.find_records("earnings_history", EARNINGS_COLUMNS, params).await;
+        }
+        check_includes(params, &["pools"], "earnings_history")?;
+        let client = self.pool.get().await?;
+        let query = QueryBuilder::with_param(params.requested_pools(), |pools| format!(
+            "SELECT e.*, COALESCE((
+               SELECT json_agg(p ORDER BY p.pool) FROM pool_earnings p
+               WHERE p.earnings_history_id = e.id AND (cardinality({0}::TEXT[]) = 0 OR p.pool = ANY({0}))
+             ), '[]') AS pools
+             FROM earnings_history e",
+            pools,
+        ))
+            .history_filters("", params)
+            .field_filters(EARNINGS_COLUMNS, params)?
+            .sort_and_page(EARNINGS_COLUMNS, params)?;
+        fetch_page(&client, &query).await
     }
 
-    pub async fn find_runepools(&self, params: &QueryParams) -> Result<Vec<RunePool>, Box<dyn std::error::Error>> {
-        self.find_records("rune_pool_history", params).await
+    pub async fn find_runepools(&self, params: &QueryParams) -> Result<Page<RunePool>, Box<dyn std::error::Error>> {
+        self.find_records("rune_pool_history", RUNEPOOL_COLUMNS, params).await
     }
 
-    pub async fn find_pool_activity(&self, pool_id: &str, params: &QueryParams) -> Result<Vec<PoolActivity>, Box<dyn std::error::Error>> {
+    pub async fn find_pool_activity(&self, pool_id: &str, params: &QueryParams) -> Result<Page<PoolActivity>, Box<dyn std::error::Error>> {
         let client = self.pool.get().await?;
-        let mut query = String::from(
-            "SELECT d.pool, d.asset_depth, d.rune_depth, d.asset_price, 
-                    COALESCE(s.to_asset_volume, 0) AS to_asset_volume, 
-                    COALESCE(s.total_fees, 0) AS total_fees, 
-                    COALESCE(s.total_volume_usd, 0) AS total_volume_usd, 
-                    d.start_time, d.end_time
-             FROM depth_price_history d
-             LEFT JOIN swaps_history s ON d.pool = s.pool AND d.start_time = s.start_time AND d.end_time = s.end_time
-             WHERE d.pool = $1"
-        );
-        let mut query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = Vec::new();
-        query_params.push(&pool_id);
-
-        if let Some(date_range) = &params.date_range {
-            query.push_str(" AND d.start_time >= $2 AND d.end_time <= $3");
-            query_params.push(&date_range.0);
-            query_params.push(&date_range.1);
-        } else {
-            if let Some(start_date) = &params.start_date {
-                query.push_str(" AND d.start_time >= $2");
-                query_params.push(start_date);
-            }
-            if let Some(end_date) = &params.end_date {
-                let param_num = if query_params.len() == 1 { 2 } else { 3 };
-                query.push_str(&format!(" AND d.end_time <= ${}", param_num));
-                query_params.push(end_date);
-            }
+        // The join is wrapped so filters and sorting can use its output column names.
+        let query = QueryBuilder::new(
+            "SELECT * FROM (
+               SELECT d.id, d.pool, d.interval, d.is_partial, d.asset_depth, d.rune_depth, d.asset_price,
+                      COALESCE(s.to_asset_volume, 0) AS to_asset_volume,
+                      COALESCE(s.total_fees, 0) AS total_fees,
+                      COALESCE(s.total_volume_usd, 0) AS total_volume_usd,
+                      d.start_time, d.end_time
+               FROM depth_price_history d
+               LEFT JOIN swaps_history s ON d.pool = s.pool AND d.interval = s.interval AND d.start_time = s.start_time AND d.end_time = s.end_time
+             ) activity"
+        )
+            .filter("pool", "=", pool_id.to_string())
+            .history_filters("", params)
+            .pool_filter(None, "pool-activity, which takes its pool from the path", params)?
+            .field_filters(POOL_ACTIVITY_COLUMNS, params)?
+            .sort_and_page(POOL_ACTIVITY_COLUMNS, params)?;
+
+        fetch_page(&client, &query).await
+    }
+
+    // The per-pool breakdown of one earnings_history row, or None when there is no such row.
+    pub async fn find_interval_pool_earnings(&self, earnings_history_id: i32) -> Result<Option<Vec<PoolEarning>>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let exists: bool = client.query_one("SELECT EXISTS (SELECT 1 FROM earnings_history WHERE id = $1)", &[&earnings_history_id]).await?.get(0);
+        if !exists {
+            return Ok(None);
         }
-        if let Some(liquidity_gt) = &params.liquidity_gt {
-            let param_num = query_params.len() + 1;
-            query.push_str(&format!(" AND d.asset_depth > ${}", param_num));
-            query_params.push(liquidity_gt);
+        let rows = client.query("SELECT * FROM pool_earnings WHERE earnings_history_id = $1 ORDER BY pool", &[&earnings_history_id]).await?;
+        Ok(Some(rows.into_iter().map(PoolEarning::from).collect()))
+    }
+
+    pub async fn find_pool_earnings(&self, pool_id: &str, params: &QueryParams) -> Result<Page<PoolEarningHistory>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        // The interval and time columns live on earnings_history, so each pool row takes them from its parent.
+        let query = QueryBuilder::new(
+            "SELECT * FROM (
+               SELECT p.id, p.earnings_history_id, p.pool, e.interval, e.start_time, e.end_time, e.is_partial,
+                      p.asset_liquidity_fees, p.rune_liquidity_fees, p.total_liquidity_fees_rune, p.saver_earning, p.rewards, p.earnings
+               FROM pool_earnings p
+               JOIN earnings_history e ON e.id = p.earnings_history_id
+             ) pool_history"
+        )
+            .filter("pool", "=", pool_id.to_string())
+            .history_filters("", params)
+            .pool_filter(None, "pool earnings, which take their pool from the path", params)?
+            .field_filters(POOL_EARNINGS_COLUMNS, params)?
+            .sort_and_page(POOL_EARNINGS_COLUMNS, params)?;
+
+        fetch_page(&client, &query).await
+    }
+
+    // Buckets of the history behind `dataset` (an endpoint name such as "swaps-history"), or None
+    // for an unknown dataset.
+    pub async fn find_aggregates(&self, dataset: &str, aggregate: &AggregateParams, params: &QueryParams) -> Result<Option<Page<Aggregate>>, Box<dyn std::error::Error>> {
+        let Some(&(_, table, columns)) = HISTORIES.iter().find(|(name, _, _)| *name == dataset) else {
+            return Ok(None);
+        };
+        let pool_column = pool_column(table);
+        let query = QueryBuilder::new(aggregate_select(table, columns, pool_column.is_some(), aggregate)?)
+            .history_filters("", params)
+            .pool_filter(pool_column, &format!("{}, which has no pool column", table), params)?
+            .field_filters(columns, params)?;
+        let query = match pool_column {
+            Some(_) => query.group_and_page(&["bucket_start", "pool"], params)?,
+            None => query.group_and_page(&["bucket_start"], params)?,
+        };
+        let client = self.pool.get().await?;
+        Ok(Some(fetch_page(&client, &query).await?))
+    }
+
+    // OHLC candles of `pool_id` per `candles.bucket`, built from the finest depth interval stored
+    // for it in the requested time range that is no coarser than the bucket. Volume is summed over
+    // the bucket from the finest such swaps interval, which need not be the same one, and is null
+    // when no swaps rows fall in the bucket. None when there is no such depth interval.
+    pub async fn find_candles(&self, pool_id: &str, candles: &CandleParams, params: &QueryParams) -> Result<Option<Page<Candle>>, Box<dyn std::error::Error>> {
+        check_no_field_filters(params, "candles")?;
+        let client = self.pool.get().await?;
+        let Some(source) = finest_stored(&client, "depth_price_history", pool_id, candles.bucket, params).await? else {
+            return Ok(None);
+        };
+        let swaps_source = finest_stored(&client, "swaps_history", pool_id, candles.bucket, params).await?;
+
+        let query = QueryBuilder::with_param(pool_id.to_string(), |pool| {
+            let volume = |column: &str| match swaps_source {
+                Some(interval) => format!(
+                    "(SELECT SUM(s.{})::NUMERIC FROM swaps_history s
+                      WHERE s.pool = {} AND s.interval = '{}' AND s.start_time >= joined.bucket_start AND s.start_time < joined.bucket_end{})",
+                    column, pool, interval, if params.exclude_partial { " AND NOT s.is_partial" } else { "" },
+                ),
+                None => "NULL::NUMERIC".to_string(),
+            };
+            format!(
+                "SELECT bucket_start, bucket_end, interval AS source_interval,
+                        (ARRAY_AGG(price ORDER BY start_time ASC))[1] AS open,
+                        MAX(price) AS high,
+                        MIN(price) AS low,
+                        (ARRAY_AGG(price ORDER BY start_time DESC))[1] AS close,
+                        {} AS volume,
+                        {} AS volume_usd,
+                        COUNT(*) AS row_count,
+                        BOOL_OR(is_partial) AS is_partial
+                 FROM (
+                   SELECT interval, start_time, end_time, is_partial, {} AS price, {}
+                   FROM depth_price_history
+                   WHERE pool = {}
+                 ) joined",
+                volume("total_volume"), volume("total_volume_usd"), candles.quote.column(), bucket_columns(candles.bucket), pool,
+            )
+        })
+            .filter("interval", "=", source.to_string())
+            .time_filters("", params)
+            .pool_filter(None, "candles, which take their pool from the path", params)?
+            .group_and_page(&["bucket_start", "bucket_end", "source_interval"], params)?;
+        Ok(Some(fetch_page(&client, &query).await?))
+    }
+
+    // Yield of `pool_id` over each window, ending at its latest closed daily depth row. Only closed
+    // daily rows count. None when the pool has no such row. Rates above 1e9 (a hundred billion
+    // percent) are null: they mean nothing, may not fit a Decimal, and are never compounded.
+    pub async fn find_yields(&self, pool_id: &str, windows: &[YieldWindow], params: &QueryParams) -> Result<Option<Vec<PoolYield>>, Box<dyn std::error::Error>> {
+        check_no_field_filters(params, "yield")?;
+        let client = self.pool.get().await?;
+        let names: Vec<&str> = windows.iter().map(|window| window.as_str()).collect();
+        let days: Vec<i32> = windows.iter().map(|window| window.days()).collect();
+        let rows = client.query(
+            "WITH windows AS (
+               SELECT w.name, w.position, latest.window_end - make_interval(days => w.days) AS window_start, latest.window_end
+               FROM (SELECT MAX(end_time) AS window_end FROM depth_price_history WHERE pool = $1 AND interval = 'day' AND NOT is_partial) latest,
+                    UNNEST($2::TEXT[], $3::INTEGER[]) WITH ORDINALITY AS w(name, days, position)
+               WHERE latest.window_end IS NOT NULL
+             ), stats AS (
+               SELECT w.name, w.position, w.window_start, w.window_end, earned.days, earned.earnings,
+                      depth.avg_depth_rune, depth.luvi_start, depth.luvi_end, depth.luvi_days
+               FROM windows w
+               CROSS JOIN LATERAL (
+                 SELECT COUNT(*) AS days, SUM(p.earnings)::NUMERIC AS earnings
+                 FROM pool_earnings p JOIN earnings_history e ON e.id = p.earnings_history_id
+                 WHERE p.pool = $1 AND e.interval = 'day' AND NOT e.is_partial
+                   AND e.start_time >= w.window_start AND e.end_time <= w.window_end
+               ) earned
+               CROSS JOIN LATERAL (
+                 SELECT ROUND(AVG(rune_depth + asset_depth * asset_price)) AS avg_depth_rune,
+                        (ARRAY_AGG(luvi ORDER BY start_time ASC))[1] AS luvi_start,
+                        (ARRAY_AGG(luvi ORDER BY start_time DESC))[1] AS luvi_end,
+                        EXTRACT(EPOCH FROM MAX(start_time) - MIN(start_time)) / 86400 AS luvi_days
+                 FROM depth_price_history
+                 WHERE pool = $1 AND interval = 'day' AND NOT is_partial
+                   AND start_time >= w.window_start AND end_time <= w.window_end
+               ) depth
+             ), rates AS (
+               SELECT *,
+                      earnings / NULLIF(avg_depth_rune, 0) * 365 / NULLIF(days, 0) AS apr,
+                      luvi_end / NULLIF(luvi_start, 0) AS luvi_ratio
+               FROM stats
+             ), annualised AS (
+               SELECT *,
+                      CASE WHEN 1 + apr / 365 <= POWER(1e9 + 1, 1.0 / 365) THEN POWER(1 + apr / 365, 365) - 1 END AS apy,
+                      (luvi_ratio - 1) * 365 / NULLIF(luvi_days, 0) AS luvi_apr,
+                      CASE WHEN luvi_ratio <= POWER(1e9 + 1, luvi_days / 365) THEN POWER(luvi_ratio, 365 / NULLIF(luvi_days, 0)) - 1 END AS luvi_apy
+               FROM rates
+             )
+             SELECT $1 AS pool, name AS window, window_start, window_end, days, earnings, avg_depth_rune,
+                    ROUND(CASE WHEN apr <= 1e9 THEN apr END, 8) AS apr,
+                    ROUND(apy, 8) AS apy,
+                    luvi_start, luvi_end,
+                    ROUND(CASE WHEN luvi_ratio - 1 <= 1e9 THEN luvi_ratio - 1 END, 8) AS luvi_growth,
+                    ROUND(CASE WHEN luvi_apr <= 1e9 THEN luvi_apr END, 8) AS luvi_apr,
+                    ROUND(luvi_apy, 8) AS luvi_apy
+             FROM annualised
+             ORDER BY position",
+            &[&pool_id, &names, &days],
+        ).await?;
+        if rows.is_empty() {
+            return Ok(None);
         }
+        Ok(Some(rows.into_iter().map(PoolYield::from).collect()))
+    }
+
+    // What a symmetric position in `pool_id` returned between the latest closed depth intervals by
+    // `lp.entry` and by `exit`, worth `lp.deposit_usd` at entry. Both come from one series:
+    // `lp.interval`, or the finest with a closed interval by the entry. None when there is none, and
+    // an error when entry and exit land on the same interval, which would report no change at all.
+    pub async fn find_lp_return(&self, pool_id: &str, lp: &LpReturnParams, exit: DateTime<Utc>) -> Result<Option<LpReturn>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let stored: Vec<String> = client.query(
+            "SELECT DISTINCT interval FROM depth_price_history WHERE pool = $1 AND NOT is_partial AND end_time <= $2",
+            &[&pool_id, &lp.entry],
+        ).await?.iter().map(|row| row.get(0)).collect();
+        let Some(interval) = Interval::ALL.into_iter()
+            .filter(|interval| lp.interval.is_none_or(|wanted| wanted == *interval))
+            .find(|interval| stored.iter().any(|s| s == interval.as_str()))
+        else {
+            return Ok(None);
+        };
 
-        if let Some(ref sort_by) = params.sort_by {
-            let order = params.order.as_deref().unwrap_or("asc");
-            query.push_str(&format!(" ORDER BY {} {}", sort_by, order));
-        } else {
-            query.push_str(" ORDER BY d.start_time DESC");
+        let row = client.query_opt(
+            "WITH states AS (
+               SELECT s.at, d.end_time, d.asset_price, d.asset_price_usd, d.asset_price_usd / d.asset_price AS rune_price_usd,
+                      d.asset_depth::NUMERIC / d.units AS asset_per_unit, d.rune_depth::NUMERIC / d.units AS rune_per_unit,
+                      SQRT(d.asset_depth::NUMERIC * d.rune_depth) / d.units AS luvi
+               FROM UNNEST(ARRAY['entry', 'exit'], ARRAY[$2::TIMESTAMPTZ, $3::TIMESTAMPTZ]) AS s(at, time)
+               CROSS JOIN LATERAL (
+                 SELECT * FROM depth_price_history
+                 WHERE pool = $1 AND interval = $5 AND NOT is_partial AND end_time <= s.time
+                   AND units > 0 AND asset_depth > 0 AND rune_depth > 0 AND asset_price > 0
+                 ORDER BY end_time DESC
+                 LIMIT 1
+               ) d
+             ), position AS (
+               SELECT e.end_time AS entry_time, x.end_time AS exit_time,
+                      e.asset_price AS entry_asset_price, x.asset_price AS exit_asset_price,
+                      e.asset_price_usd AS entry_asset_price_usd, x.asset_price_usd AS exit_asset_price_usd,
+                      e.rune_price_usd AS entry_rune_price_usd, x.rune_price_usd AS exit_rune_price_usd,
+                      x.asset_price / e.asset_price AS price_ratio,
+                      e.luvi AS entry_luvi, x.luvi AS exit_luvi,
+                      $4::NUMERIC / 2 / e.asset_price_usd AS entry_asset_amount,
+                      $4::NUMERIC / 2 / e.rune_price_usd AS entry_rune_amount,
+                      $4::NUMERIC / 2 / e.asset_price_usd * x.asset_per_unit / e.asset_per_unit AS exit_asset_amount,
+                      $4::NUMERIC / 2 / e.rune_price_usd * x.rune_per_unit / e.rune_per_unit AS exit_rune_amount,
+                      x.asset_price_usd, x.rune_price_usd
+               FROM states e, states x
+               WHERE e.at = 'entry' AND x.at = 'exit'
+             ), returns AS (
+               SELECT *,
+                      exit_asset_amount * asset_price_usd + exit_rune_amount * rune_price_usd AS lp_value_usd,
+                      entry_asset_amount * asset_price_usd + entry_rune_amount * rune_price_usd AS hold_value_usd,
+                      2 * SQRT(price_ratio) / (1 + price_ratio) - 1 AS impermanent_loss,
+                      exit_luvi / entry_luvi - 1 AS luvi_growth
+               FROM position
+             )
+             SELECT $1 AS pool, $5 AS interval, entry_time, exit_time,
+                    entry_asset_price, exit_asset_price, entry_asset_price_usd, exit_asset_price_usd,
+                    ROUND(entry_rune_price_usd, 8) AS entry_rune_price_usd, ROUND(exit_rune_price_usd, 8) AS exit_rune_price_usd,
+                    ROUND(price_ratio, 8) AS price_ratio,
+                    ROUND(entry_luvi, 8) AS entry_luvi, ROUND(exit_luvi, 8) AS exit_luvi,
+                    ROUND(luvi_growth, 8) AS luvi_growth,
+                    ROUND(impermanent_loss, 8) AS impermanent_loss,
+                    ROUND(lp_value_usd / hold_value_usd - 1, 8) AS lp_vs_hold,
+                    $4::NUMERIC AS deposit_usd,
+                    ROUND(entry_asset_amount, 8) AS entry_asset_amount, ROUND(entry_rune_amount, 8) AS entry_rune_amount,
+                    ROUND(exit_asset_amount, 8) AS exit_asset_amount, ROUND(exit_rune_amount, 8) AS exit_rune_amount,
+                    ROUND(lp_value_usd, 8) AS lp_value_usd, ROUND(hold_value_usd, 8) AS hold_value_usd,
+                    ROUND(lp_value_usd / $4::NUMERIC - 1, 8) AS lp_return,
+                    ROUND(hold_value_usd / $4::NUMERIC - 1, 8) AS hold_return
+             FROM returns",
+            &[&pool_id, &lp.entry, &exit, &lp.deposit_usd, &interval.as_str()],
+        ).await?;
+        let Some(lp_return) = row.map(LpReturn::from) else {
+            return Ok(None);
+        };
+        if lp_return.entry_time == lp_return.exit_time {
+            return Err(Box::new(QueryError::SameInterval { interval: lp_return.interval, end_time: lp_return.exit_time }));
         }
+        Ok(Some(lp_return))
+    }
 
-        let limit = params.limit.unwrap_or(10).min(100);
-        let offset = params.page.unwrap_or(0) * limit;
-        query.push_str(&format!(" LIMIT ${} OFFSET ${}", query_params.len() + 1, query_params.len() + 2));
-        query_params.push(&limit);
-        query_params.push(&offset);
+    // Whether any depth history was ever stored for `pool_id`, at any interval.
+    pub async fn pool_exists(&self, pool_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let row = client.query_one("SELECT EXISTS (SELECT 1 FROM depth_price_history WHERE pool = $1)", &[&pool_id]).await?;
+        Ok(row.get(0))
+    }
 
-        let rows = client.query(&query, &query_params[..]).await?;
-        Ok(rows.into_iter().map(PoolActivity::from).collect())
+    // Latest run, last success and last failure per dataset and interval, plus row and request
+    // totals over every pool's runs of the last 24 hours.
+    pub async fn find_ingestion_status(&self) -> Result<Vec<IngestionStatus>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let rows = client.query(
+            "SELECT dataset, interval,
+                    MAX(started_at) AS last_run_at,
+                    MAX(finished_at) FILTER (WHERE error IS NULL) AS last_success_at,
+                    (ARRAY_AGG(error ORDER BY started_at DESC) FILTER (WHERE error IS NOT NULL))[1] AS last_error,
+                    (ARRAY_AGG(pool ORDER BY started_at DESC) FILTER (WHERE error IS NOT NULL))[1] AS last_error_pool,
+                    MAX(finished_at) FILTER (WHERE error IS NOT NULL) AS last_error_at,
+                    COALESCE(SUM(rows_inserted) FILTER (WHERE started_at > NOW() - INTERVAL '24 hours'), 0)::BIGINT AS rows_inserted_24h,
+                    COALESCE(SUM(rows_updated) FILTER (WHERE started_at > NOW() - INTERVAL '24 hours'), 0)::BIGINT AS rows_updated_24h,
+                    COALESCE(SUM(http_attempts) FILTER (WHERE started_at > NOW() - INTERVAL '24 hours'), 0)::BIGINT AS http_attempts_24h
+             FROM ingestion_runs
+             GROUP BY dataset, interval
+             ORDER BY dataset, interval",
+            &[],
+        ).await?;
+        Ok(rows.into_iter().map(IngestionStatus::from).collect())
     }
 
-    async fn find_records<T: From<tokio_postgres::Row> + Send + Sync>(&self, table: &str, params: &QueryParams) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    pub async fn find_unresolved_gaps(&self) -> Result<Vec<IntervalGap>, Box<dyn std::error::Error>> {
         let client = self.pool.get().await?;
-        let query = build_query(table, params);
-        let rows = client.query(&query, &[]).await?;
-        Ok(rows.into_iter().map(T::from).collect())
+        let rows = client.query(
+            "SELECT * FROM interval_gaps WHERE resolved_at IS NULL ORDER BY dataset, pool, interval, gap_start",
+            &[],
+        ).await?;
+        Ok(rows.into_iter().map(IntervalGap::from).collect())
     }
+
+    async fn find_records<T: From<tokio_postgres::Row> + Send + Sync>(&self, table: &str, columns: &'static [&'static str], params: &QueryParams) -> Result<Page<T>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let pool_column = pool_column(table);
+        check_includes(params, &[], table)?;
+        let query = QueryBuilder::new(format!("SELECT * FROM {}", table))
+            .history_filters("", params)
+            .pool_filter(pool_column, &format!("{}, which has no pool column", table), params)?
+            .field_filters(columns, params)?
+            .sort_and_page(columns, params)?;
+        fetch_page(&client, &query).await
+    }
+}
+
+// The finest interval of `table` stored for `pool_id` in the requested range, up to `bucket`.
+async fn finest_stored(client: &deadpool_postgres::Client, table: &str, pool_id: &str, bucket: Interval, params: &QueryParams) -> Result<Option<Interval>, Box<dyn std::error::Error>> {
+    let query = QueryBuilder::new(format!("SELECT DISTINCT interval FROM {}", table))
+        .filter("pool", "=", pool_id.to_string())
+        .time_filters("", params);
+    let stored: Vec<String> = client.query(&query.sql(), &query.params()).await?.iter().map(|row| row.get(0)).collect();
+    Ok(Interval::ALL.into_iter()
+        .take_while(|interval| *interval != bucket)
+        .chain([bucket])
+        .find(|interval| stored.iter().any(|s| s == interval.as_str())))
 }
 
-fn build_query(table: &str, params: &QueryParams) -> String {
-    let mut query = format!("SELECT * FROM {}", table);
-    let mut conditions = Vec::new();
+// The column splitting `table` by pool, if it has one.
+fn pool_column(table: &str) -> Option<&'static str> {
+    matches!(table, "depth_price_history" | "swaps_history").then_some("pool")
+}
 
-    if let Some((start, end)) = params.date_range {
-        conditions.push(format!("start_time >= '{}'", start));
-        conditions.push(format!("end_time <= '{}'", end));
-    } else {
-        if let Some(start_date) = params.start_date {
-            conditions.push(format!("start_time >= '{}'", start_date));
-        }
-        if let Some(end_date) = params.end_date {
-            conditions.push(format!("end_time <= '{}'", end_date));
+// Runs a paged query and the count of everything it matches.
+async fn fetch_page<T: From<Row>>(client: &deadpool_postgres::Client, query: &QueryBuilder) -> Result<Page<T>, Box<dyn std::error::Error>> {
+    let rows = client.query(&query.sql(), &query.params()).await?;
+    let total_count: i64 = client.query_one(&query.count_sql(), &query.count_params()).await?.get(0);
+    Ok(query.paginate(rows, total_count))
+}
+
+#[cfg(test)]
+pub fn test_pool() -> Pool {
+    use deadpool_postgres::{Config as PgConfig, Runtime};
+
+    let mut pg_config = PgConfig::new();
+    pg_config.dbname = Some("api".to_string());
+    pg_config.host = Some("localhost".to_string());
+    pg_config.user = Some("postgres".to_string());
+    pg_config.password = Some("Bhakwaas@csd37".to_string());
+    pg_config.create_pool(Some(Runtime::Tokio1), tokio_postgres::NoTls).unwrap()
+}
+
+// A closed depth row of `pool` with every amount 1, for tests to adjust with struct update syntax.
+#[cfg(test)]
+pub fn test_depth(pool: &str, interval: &str, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> DepthPrice {
+    use rust_decimal::Decimal;
+
+    DepthPrice {
+        id: 0,
+        pool: pool.to_string(),
+        interval: interval.to_string(),
+        start_time,
+        end_time,
+        is_partial: false,
+        asset_depth: 1,
+        rune_depth: 1,
+        asset_price: Decimal::ONE,
+        asset_price_usd: Decimal::ONE,
+        liquidity_units: 1,
+        members_count: 1,
+        synth_units: 1,
+        synth_supply: 1,
+        units: 1,
+        luvi: Decimal::ONE,
+    }
+}
+
+#[cfg(test)]
+pub async fn insert_test_depths(pool: &Pool, depths: &[DepthPrice]) {
+    let client = pool.get().await.unwrap();
+    for d in depths {
+        client.execute(
+            "INSERT INTO depth_price_history (pool, interval, start_time, end_time, is_partial, asset_depth, rune_depth, asset_price, asset_price_usd,
+                                              liquidity_units, members_count, synth_units, synth_supply, units, luvi)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+            &[&d.pool, &d.interval, &d.start_time, &d.end_time, &d.is_partial, &d.asset_depth, &d.rune_depth, &d.asset_price, &d.asset_price_usd,
+              &d.liquidity_units, &d.members_count, &d.synth_units, &d.synth_supply, &d.units, &d.luvi],
+        ).await.unwrap();
+    }
+}
+
+// Deletes every row of a test-only pool. Each test seeds a pool of its own, so tests running in
+// parallel never see or delete each other's rows; they call this before seeding and when done.
+#[cfg(test)]
+pub async fn remove_test_pool(pool: &Pool, name: &str) {
+    let client = pool.get().await.unwrap();
+    for table in ["depth_price_history", "swaps_history", "pool_earnings"] {
+        client.execute(&format!("DELETE FROM {} WHERE pool = $1", table), &[&name]).await.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FieldFilter;
+    use actix_web::web::Query;
+    use chrono::Duration;
+    use rust_decimal::Decimal;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    // Hourly prices 10, 14, 8, 12 and a daily row the candles must not fall back to, with swaps
+    // stored for the day only.
+    async fn seed_candle_pool(pool: &Pool) {
+        remove_test_pool(pool, "CANDLE.TEST").await;
+        let amounts = &SWAP_COLUMNS[6..];
+        let values: Vec<&str> = amounts.iter().map(|column| if column.starts_with("total_volume") { "500" } else { "0" }).collect();
+        pool.get().await.unwrap().execute(
+            &format!("INSERT INTO swaps_history (pool, interval, start_time, end_time, {}) VALUES ('CANDLE.TEST', 'day', '2023-01-02', '2023-01-03', {})", amounts.join(", "), values.join(", ")),
+            &[],
+        ).await.unwrap();
+        let day = at("2023-01-02T00:00:00Z");
+        let depths: Vec<DepthPrice> = [("hour", 0, 10), ("hour", 1, 14), ("hour", 2, 8), ("hour", 3, 12), ("day", 0, 99)].into_iter()
+            .map(|(interval, hour, price)| DepthPrice {
+                asset_price: price.into(),
+                asset_price_usd: price.into(),
+                ..test_depth("CANDLE.TEST", interval, day + Duration::hours(hour), day + Duration::hours(hour + 1))
+            })
+            .collect();
+        insert_test_depths(pool, &depths).await;
+    }
+
+    #[actix_rt::test]
+    async fn builds_candles_from_the_finest_stored_interval() {
+        let pool = test_pool();
+        seed_candle_pool(&pool).await;
+        let db = Database::new(pool);
+        let params = Query::<QueryParams>::from_query("").unwrap().into_inner();
+        let candles = Query::<CandleParams>::from_query("bucket=day").unwrap().into_inner();
+
+        let page = db.find_candles("CANDLE.TEST", &candles, &params).await.unwrap().unwrap();
+
+        assert_eq!(page.items.len(), 1);
+        let candle = &page.items[0];
+        assert_eq!(candle.source_interval, "hour");
+        let ohlc = [candle.open, candle.high, candle.low, candle.close].map(|price| price.to_string());
+        assert_eq!(ohlc, ["10", "14", "8", "12"]);
+        assert_eq!(candle.row_count, 4);
+        assert_eq!(candle.volume.map(|volume| volume.to_string()).as_deref(), Some("500"));
+
+        let hourly = Query::<CandleParams>::from_query("bucket=hour").unwrap().into_inner();
+        let page = db.find_candles("CANDLE.TEST", &hourly, &params).await.unwrap().unwrap();
+        assert_eq!(page.items.len(), 4);
+        assert!(page.items.iter().all(|candle| candle.volume.is_none() && candle.volume_usd.is_none()));
+
+        let too_fine = Query::<CandleParams>::from_query("bucket=5min").unwrap().into_inner();
+        assert!(db.find_candles("CANDLE.TEST", &too_fine, &params).await.unwrap().is_none());
+
+        let filtered = Query::<QueryParams>::from_query("").unwrap().into_inner();
+        let filtered = QueryParams { filters: FieldFilter::parse_query("asset_price[gt]=1").unwrap(), ..filtered };
+        let err = db.find_candles("CANDLE.TEST", &candles, &filtered).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<QueryError>(), Some(QueryError::FieldFilterUnsupported { .. })), "{}", err);
+        remove_test_pool(&db.pool, "CANDLE.TEST").await;
+    }
+
+    // Eight closed days from `start` worth 10,000 RUNE each (5,000 RUNE plus 2,500 of the asset at
+    // 2), earning 10 a day, with LUVI rising by 0.01 a day, and an open day that must be ignored.
+    // Each test passes its own pool and start, as the earnings_history rows are shared by all pools.
+    async fn seed_yield_pool(pool: &Pool, name: &str, start: &str) {
+        remove_yield_pool(pool, name, start).await;
+        let start = at(start);
+        let depths: Vec<DepthPrice> = (0..9)
+            .map(|day| DepthPrice {
+                is_partial: day == 8,
+                asset_depth: 2500,
+                rune_depth: 5000,
+                asset_price: 2.into(),
+                asset_price_usd: 2.into(),
+                luvi: Decimal::ONE + Decimal::new(day, 2),
+                ..test_depth(name, "day", start + Duration::days(day), start + Duration::days(day + 1))
+            })
+            .collect();
+        insert_test_depths(pool, &depths).await;
+        let client = pool.get().await.unwrap();
+        for depth in &depths {
+            let id: i32 = client.query_one(
+                "INSERT INTO earnings_history (interval, start_time, end_time, is_partial, liquidity_fees, block_rewards, earnings,
+                                               bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd)
+                 VALUES ('day', $1, $2, $3, 0, 0, 0, 0, 0, 1, 1)
+                 RETURNING id",
+                &[&depth.start_time, &depth.end_time, &depth.is_partial],
+            ).await.unwrap().get(0);
+            client.execute(
+                "INSERT INTO pool_earnings (earnings_history_id, pool, asset_liquidity_fees, rune_liquidity_fees, total_liquidity_fees_rune, saver_earning, rewards, earnings)
+                 VALUES ($1, $2, 0, 0, 0, 0, 0, 10)",
+                &[&id, &name],
+            ).await.unwrap();
         }
     }
-    if let Some(liquidity_gt) = params.liquidity_gt {
-        if table == "depth_price_history" {
-            conditions.push(format!("asset_depth > {}", liquidity_gt));
+
+    async fn remove_yield_pool(pool: &Pool, name: &str, start: &str) {
+        remove_test_pool(pool, name).await;
+        let start = at(start);
+        pool.get().await.unwrap().execute(
+            "DELETE FROM earnings_history WHERE interval = 'day' AND start_time >= $1 AND start_time < $2",
+            &[&start, &(start + Duration::days(9))],
+        ).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn computes_yield_over_closed_days_of_the_window() {
+        let pool = test_pool();
+        seed_yield_pool(&pool, "YIELD.TEST", "2001-01-01T00:00:00Z").await;
+        let db = Database::new(pool);
+        let params = Query::<QueryParams>::from_query("").unwrap().into_inner();
+
+        let yields = db.find_yields("YIELD.TEST", &[YieldWindow::Week, YieldWindow::Month], &params).await.unwrap().unwrap();
+
+        let windows: Vec<&str> = yields.iter().map(|y| y.window.as_str()).collect();
+        assert_eq!(windows, ["7d", "30d"]);
+        let week = &yields[0];
+        assert_eq!(week.days, 7);
+        assert_eq!(week.avg_depth_rune.unwrap().to_string(), "10000");
+        // 70 earned on 10,000 over 7 days.
+        assert_eq!(week.apr.unwrap().normalize().to_string(), "0.365");
+        assert!(week.apy.unwrap() > week.apr.unwrap());
+        assert_eq!((week.luvi_start.unwrap().normalize().to_string(), week.luvi_end.unwrap().normalize().to_string()), ("1.01".to_string(), "1.07".to_string()));
+        assert_eq!(yields[1].days, 8);
+
+        assert!(db.find_yields("NO.SUCH.POOL", &[YieldWindow::Week], &params).await.unwrap().is_none());
+        let filtered = Query::<QueryParams>::from_query("liquidity_gt=1").unwrap().into_inner();
+        assert!(db.find_yields("YIELD.TEST", &[YieldWindow::Week], &filtered).await.is_err());
+        remove_yield_pool(&db.pool, "YIELD.TEST", "2001-01-01T00:00:00Z").await;
+    }
+
+    #[actix_rt::test]
+    async fn rejects_liquidity_filter_on_data_without_depth() {
+        let db = Database::new(test_pool());
+        let params = Query::<QueryParams>::from_query("liquidity_gt=999999999999999999").unwrap().into_inner();
+
+        let errors = [
+            db.find_swaps(&params).await.map(|_| ()),
+            db.find_earnings(&params).await.map(|_| ()),
+            db.find_runepools(&params).await.map(|_| ()),
+        ];
+
+        for error in errors {
+            let error = error.unwrap_err();
+            assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::FieldFilterUnsupported { filter: "liquidity_gt", .. })), "{}", error);
         }
+        assert_eq!(db.find_depths(&params).await.unwrap().total_count, 0);
     }
 
-    if !conditions.is_empty() {
-        query.push_str(" WHERE ");
-        query.push_str(&conditions.join(" AND "));
+    // LUVI growing a thousandfold in one day compounds past anything a Decimal holds.
+    #[actix_rt::test]
+    async fn nulls_rates_too_large_to_report() {
+        let pool = test_pool();
+        remove_test_pool(&pool, "STEEP.TEST").await;
+        let start = at("2001-04-01T00:00:00Z");
+        let depths: Vec<DepthPrice> = [1, 1000].into_iter().zip(0..)
+            .map(|(luvi, day)| DepthPrice { luvi: luvi.into(), ..test_depth("STEEP.TEST", "day", start + Duration::days(day), start + Duration::days(day + 1)) })
+            .collect();
+        insert_test_depths(&pool, &depths).await;
+        let db = Database::new(pool);
+        let params = Query::<QueryParams>::from_query("").unwrap().into_inner();
+
+        let yields = db.find_yields("STEEP.TEST", &[YieldWindow::Week], &params).await.unwrap().unwrap();
+
+        assert_eq!(yields[0].luvi_growth.unwrap().normalize().to_string(), "999");
+        assert_eq!(yields[0].luvi_apr.unwrap().normalize().to_string(), "364635");
+        assert_eq!(yields[0].luvi_apy, None);
+        remove_test_pool(&db.pool, "STEEP.TEST").await;
     }
 
-    if let Some(ref sort_by) = params.sort_by {
-        let order = params.order.as_deref().unwrap_or("asc");
-        query.push_str(&format!(" ORDER BY {} {}", sort_by, order));
-    } else {
-        query.push_str(" ORDER BY start_time DESC");
+    #[actix_rt::test]
+    async fn reads_pool_earnings_per_interval_and_per_pool() {
+        let pool = test_pool();
+        seed_yield_pool(&pool, "EARNINGS.TEST", "2001-02-01T00:00:00Z").await;
+        let db = Database::new(pool);
+        let params = Query::<QueryParams>::from_query("exclude_partial=true&order=desc&limit=3").unwrap().into_inner();
+
+        let page = db.find_pool_earnings("EARNINGS.TEST", &params).await.unwrap();
+
+        assert_eq!(page.total_count, 8);
+        let starts: Vec<String> = page.items.iter().map(|earning| earning.start_time.format("%Y-%m-%d").to_string()).collect();
+        assert_eq!(starts, ["2001-02-08", "2001-02-07", "2001-02-06"]);
+
+        let interval = db.find_interval_pool_earnings(page.items[0].earnings_history_id).await.unwrap().unwrap();
+        assert!(interval.iter().any(|earning| earning.pool == "EARNINGS.TEST" && earning.earnings == 10));
+        assert!(db.find_interval_pool_earnings(-1).await.unwrap().is_none());
+        remove_yield_pool(&db.pool, "EARNINGS.TEST", "2001-02-01T00:00:00Z").await;
+    }
+
+    #[actix_rt::test]
+    async fn nests_requested_pool_earnings_in_earnings_history() {
+        let pool = test_pool();
+        seed_yield_pool(&pool, "NESTED.TEST", "2001-03-01T00:00:00Z").await;
+        let db = Database::new(pool);
+        let range = "date_range=2001-03-01T00:00:00Z,2001-03-04T00:00:00Z";
+
+        let params = Query::<QueryParams>::from_query(&format!("{}&include=pools&pool=NESTED.TEST", range)).unwrap().into_inner();
+        let page = db.find_earnings(&params).await.unwrap();
+        assert_eq!(page.total_count, 3);
+        for earnings in &page.items {
+            let pools = earnings.pools.as_ref().unwrap();
+            assert_eq!(pools.iter().map(|p| (p.pool.as_str(), p.earnings)).collect::<Vec<_>>(), [("NESTED.TEST", 10)]);
+            assert_eq!(pools[0].earnings_history_id, earnings.id);
+        }
+
+        let params = Query::<QueryParams>::from_query(range).unwrap().into_inner();
+        assert!(db.find_earnings(&params).await.unwrap().items.iter().all(|earnings| earnings.pools.is_none()));
+        let params = Query::<QueryParams>::from_query("include=pools").unwrap().into_inner();
+        assert!(db.find_depths(&params).await.is_err());
+        remove_yield_pool(&db.pool, "NESTED.TEST", "2001-03-01T00:00:00Z").await;
     }
 
-    let limit = params.limit.unwrap_or(10).min(100);
-    let offset = params.page.unwrap_or(0) * limit;
-    query.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
+    // The asset quadruples in RUNE and USD while the RUNE price stays at $2, and fees grow the
+    // pool's sqrt(asset_depth * rune_depth) from 100 to 110 over the same 100 units. A still-open
+    // day and a coarser month row must not be picked as the exit.
+    #[actix_rt::test]
+    async fn splits_lp_return_into_impermanent_loss_and_luvi_growth() {
+        let pool = test_pool();
+        remove_test_pool(&pool, "LP.TEST").await;
+        let start = at("2002-01-01T00:00:00Z");
+        let rows = [("day", 0, 1, false, 100, 100, 1), ("day", 1, 1, false, 55, 220, 4), ("day", 2, 1, true, 10, 1000, 100), ("month", 0, 3, false, 10, 1000, 100)];
+        let depths: Vec<DepthPrice> = rows.into_iter()
+            .map(|(interval, day, days, is_partial, asset_depth, rune_depth, price)| DepthPrice {
+                is_partial,
+                asset_depth,
+                rune_depth,
+                asset_price: price.into(),
+                asset_price_usd: (price * 2).into(),
+                units: 100,
+                ..test_depth("LP.TEST", interval, start + Duration::days(day), start + Duration::days(day + days))
+            })
+            .collect();
+        insert_test_depths(&pool, &depths).await;
+        let db = Database::new(pool);
+        let params = |query: &str| Query::<LpReturnParams>::from_query(query).unwrap().into_inner();
+
+        let lp = db.find_lp_return("LP.TEST", &params("entry=2002-01-02T00:00:00Z"), at("2002-01-05T00:00:00Z")).await.unwrap().unwrap();
+
+        let values = [lp.price_ratio, lp.impermanent_loss, lp.luvi_growth, lp.lp_vs_hold, lp.exit_asset_amount, lp.exit_rune_amount, lp.lp_value_usd, lp.hold_value_usd]
+            .map(|value| value.normalize().to_string());
+        assert_eq!(values, ["4", "-0.2", "0.1", "-0.12", "137.5", "550", "2200", "2500"]);
+        assert_eq!((lp.interval.as_str(), lp.exit_time), ("day", at("2002-01-03T00:00:00Z")));
 
-    query
-}
\ No newline at end of file
+        assert!(db.find_lp_return("LP.TEST", &params("entry=2002-01-01T12:00:00Z"), at("2002-01-05T00:00:00Z")).await.unwrap().is_none());
+        assert!(db.find_lp_return("LP.TEST", &params("entry=2002-01-02T00:00:00Z&interval=month"), at("2002-01-05T00:00:00Z")).await.unwrap().is_none());
+        let same = db.find_lp_return("LP.TEST", &params("entry=2002-01-02T06:00:00Z"), at("2002-01-02T18:00:00Z")).await.unwrap_err();
+        assert!(matches!(same.downcast_ref::<QueryError>(), Some(QueryError::SameInterval { .. })), "{}", same);
+        remove_test_pool(&db.pool, "LP.TEST").await;
+    }
+}
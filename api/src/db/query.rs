@@ -0,0 +1,543 @@
+use crate::models::{AggregateParams, Cursor, CursorDirection, FilterOp, Interval, Page, QueryParams, SortOrder};
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+use tokio_postgres::Row;
+use tokio_postgres::types::ToSql;
+
+// Request parameters the builder refuses; routes answer these with 422 rather than 500.
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("cannot sort by `{column}`; expected one of: {}", allowed.join(", "))]
+    UnknownSortColumn { column: String, allowed: &'static [&'static str] },
+    #[error("`pool` and `pools` filters are not supported by {0}")]
+    PoolFilterUnsupported(String),
+    #[error("`{filter}` filters are not supported by {endpoint}")]
+    FieldFilterUnsupported { filter: &'static str, endpoint: String },
+    #[error("cannot filter on `{column}`; expected one of: {}", allowed.join(", "))]
+    UnknownFilterColumn { column: String, allowed: Vec<&'static str> },
+    #[error("`cursor` pages by start_time and cannot be combined with sort_by={0}")]
+    CursorWithSort(String),
+    #[error("pass either `cursor` or `page`, not both")]
+    CursorWithPage,
+    #[error("cannot sort by `{0}`; bucketed results are ordered by bucket_start, in `order`")]
+    BucketSort(String),
+    #[error("`cursor` is not supported by {0}; use `page`")]
+    CursorUnsupported(String),
+    #[error("`fields` must name at least one of: {}", allowed.join(", "))]
+    NoAggregateFields { allowed: Vec<&'static str> },
+    #[error("cannot aggregate `{column}`; expected one of: {}", allowed.join(", "))]
+    UnknownAggregateField { column: String, allowed: Vec<&'static str> },
+    #[error("cannot include `{include}` in {endpoint}")]
+    UnknownInclude { include: String, endpoint: String },
+    #[error("entry and exit both fall in the {interval} interval ending {end_time}; pick a later exit or a finer `interval`")]
+    SameInterval { interval: String, end_time: DateTime<Utc> },
+}
+
+// Columns that are sortable but not numeric, so not open to `column[op]=value` filters.
+const NON_NUMERIC: &[&str] = &["id", "pool", "interval", "start_time", "end_time", "is_partial"];
+
+fn numeric(columns: &'static [&'static str]) -> Vec<&'static str> {
+    columns.iter().copied().filter(|c| !NON_NUMERIC.contains(c)).collect()
+}
+
+// A SELECT rolling `aggregate.fields` of `table` up per bucket of start_time, plus the pool column
+// when `pooled`, to be grouped by `bucket_start` (and `pool`). Only whitelisted numeric columns of
+// `columns` make it into the SQL.
+pub fn aggregate_select(table: &str, columns: &'static [&'static str], pooled: bool, aggregate: &AggregateParams) -> Result<String, QueryError> {
+    let numeric = numeric(columns);
+    let fields = aggregate.fields.as_deref().unwrap_or_default();
+    if fields.is_empty() {
+        return Err(QueryError::NoAggregateFields { allowed: numeric });
+    }
+    let mut select = format!("SELECT {}, COUNT(*) AS row_count, BOOL_OR(is_partial) AS is_partial", bucket_columns(aggregate.bucket));
+    if pooled {
+        select.push_str(", pool");
+    }
+    for field in fields {
+        let column = *numeric.iter().find(|c| **c == field.as_str()).ok_or_else(|| QueryError::UnknownAggregateField {
+            column: field.clone(),
+            allowed: numeric.clone(),
+        })?;
+        select.push_str(&format!(", {} AS {}", aggregate.agg.as_sql(column), column));
+    }
+    select.push_str(&format!(" FROM {}", table));
+    Ok(select)
+}
+
+// Rejects any `include` that is not one of `allowed` for `endpoint`.
+pub fn check_includes(params: &QueryParams, allowed: &[&str], endpoint: &str) -> Result<(), QueryError> {
+    match params.include.iter().flatten().find(|include| !allowed.contains(&include.as_str())) {
+        Some(include) => Err(QueryError::UnknownInclude { include: include.clone(), endpoint: endpoint.to_string() }),
+        None => Ok(()),
+    }
+}
+
+// For endpoints whose rows are computed rather than stored, where a value filter has no column to
+// apply to and would otherwise be silently ignored.
+pub fn check_no_field_filters(params: &QueryParams, endpoint: &str) -> Result<(), QueryError> {
+    let filter = match (params.filters.is_empty(), params.liquidity_gt) {
+        (false, _) => "column[op]=value",
+        (true, Some(_)) => "liquidity_gt",
+        (true, None) => return Ok(()),
+    };
+    Err(QueryError::FieldFilterUnsupported { filter, endpoint: endpoint.to_string() })
+}
+
+// The `bucket_start` and `bucket_end` columns of the `bucket` each start_time falls in, aligned to
+// UTC calendar boundaries.
+pub fn bucket_columns(bucket: Interval) -> String {
+    let start = match bucket {
+        Interval::FiveMin => "to_timestamp(floor(extract(epoch FROM start_time) / 300) * 300)".to_string(),
+        bucket => format!("date_trunc('{}', start_time AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'", bucket),
+    };
+    format!("{0} AS bucket_start, {0} + INTERVAL '{1}' AS bucket_end", start, bucket.as_pg_interval())
+}
+
+// Builds a SELECT whose values are all bound as `$n` parameters. Only column names chosen by the
+// caller (never by the request) are written into the SQL text.
+pub struct QueryBuilder {
+    select: String,
+    conditions: Vec<String>,
+    group_by: Option<String>,
+    order_by: Option<String>,
+    page: Option<(String, String)>,
+    paging: Option<Paging>,
+    params: Vec<Box<dyn ToSql + Sync + Send>>,
+}
+
+// What `sort_and_page` decided, for `paginate` and `count_sql`.
+struct Paging {
+    limit: i64,
+    page: Option<i64>,
+    offset: i64,
+    // Conditions and parameters that existed before paging added the cursor's, which the total
+    // count must leave out.
+    filters: (usize, usize),
+    keyset: Option<Keyset>,
+}
+
+// How a start_time-ordered page was read, so `paginate` can drop the look-ahead row and hand out
+// cursors.
+struct Keyset {
+    reversed: bool,
+    has_prev: bool,
+}
+
+impl QueryBuilder {
+    pub fn new(select: impl Into<String>) -> Self {
+        Self { select: select.into(), conditions: Vec::new(), group_by: None, order_by: None, page: None, paging: None, params: Vec::new() }
+    }
+
+    // For a SELECT that uses a request value itself, e.g. in a subquery: `value` is bound first and
+    // `select` is given its placeholder.
+    pub fn with_param(value: impl ToSql + Sync + Send + 'static, select: impl FnOnce(&str) -> String) -> Self {
+        let mut builder = Self::new(String::new());
+        let placeholder = builder.bind(value);
+        builder.select = select(&placeholder);
+        builder
+    }
+
+    // Adds `value` as the next parameter and returns its placeholder.
+    fn bind(&mut self, value: impl ToSql + Sync + Send + 'static) -> String {
+        self.params.push(Box::new(value));
+        format!("${}", self.params.len())
+    }
+
+    pub fn filter(mut self, column: &str, op: &str, value: impl ToSql + Sync + Send + 'static) -> Self {
+        let placeholder = self.bind(value);
+        self.conditions.push(format!("{} {} {}", column, op, placeholder));
+        self
+    }
+
+    // A fixed condition with no request values in it, such as `is_partial = FALSE`.
+    pub fn condition(mut self, sql: impl Into<String>) -> Self {
+        self.conditions.push(sql.into());
+        self
+    }
+
+    // The interval, partial and date filters every history endpoint accepts, on columns
+    // qualified by `prefix` (e.g. "d." in a join).
+    pub fn history_filters(self, prefix: &str, params: &QueryParams) -> Self {
+        self.filter(&format!("{}interval", prefix), "=", params.interval.to_string())
+            .time_filters(prefix, params)
+    }
+
+    // `history_filters` without the interval, for queries that pick their own.
+    pub fn time_filters(mut self, prefix: &str, params: &QueryParams) -> Self {
+        if params.exclude_partial {
+            self = self.condition(format!("{}is_partial = FALSE", prefix));
+        }
+        let (start, end) = match params.date_range {
+            Some((start, end)) => (Some(start), Some(end)),
+            None => (params.start_date, params.end_date),
+        };
+        if let Some(start) = start {
+            self = self.filter(&format!("{}start_time", prefix), ">=", start);
+        }
+        if let Some(end) = end {
+            self = self.filter(&format!("{}end_time", prefix), "<=", end);
+        }
+        self
+    }
+
+    // Restricts `column` to the pools named by `pool`/`pools`. `column` is None for data that is not
+    // split by pool, where asking for a pool is an error rather than a silently ignored filter.
+    pub fn pool_filter(mut self, column: Option<&str>, source: &str, params: &QueryParams) -> Result<Self, QueryError> {
+        let pools = params.requested_pools();
+        match column {
+            _ if pools.is_empty() => {}
+            Some(column) => {
+                let placeholder = self.bind(pools);
+                self.conditions.push(format!("{} = ANY({})", column, placeholder));
+            }
+            None => return Err(QueryError::PoolFilterUnsupported(source.to_string())),
+        }
+        Ok(self)
+    }
+
+    // The `column[op]=value` filters and `liquidity_gt`, limited to the numeric columns among
+    // `columns`. Values are compared as NUMERIC so one cast serves BIGINT and NUMERIC columns.
+    pub fn field_filters(mut self, columns: &'static [&'static str], params: &QueryParams) -> Result<Self, QueryError> {
+        let numeric = numeric(columns);
+        for filter in &params.filters {
+            let column = *numeric.iter().find(|c| **c == filter.column).ok_or_else(|| QueryError::UnknownFilterColumn {
+                column: filter.column.clone(),
+                allowed: numeric.clone(),
+            })?;
+            let condition = match filter.op {
+                FilterOp::Between => {
+                    let low = self.bind(filter.values[0]);
+                    let high = self.bind(filter.values[1]);
+                    format!("{} BETWEEN {}::NUMERIC AND {}::NUMERIC", column, low, high)
+                }
+                op => {
+                    let value = self.bind(filter.values[0]);
+                    format!("{} {} {}::NUMERIC", column, op.as_sql(), value)
+                }
+            };
+            self.conditions.push(condition);
+        }
+        if let Some(liquidity_gt) = params.liquidity_gt {
+            if !numeric.contains(&"asset_depth") {
+                return Err(QueryError::FieldFilterUnsupported { filter: "liquidity_gt", endpoint: "data without an asset_depth column".to_string() });
+            }
+            self = self.filter("asset_depth", ">", liquidity_gt);
+        }
+        Ok(self)
+    }
+
+    // Orders by `sort_by` when it names one of `sortable`, newest first otherwise, then applies
+    // `limit` (10 rows by default, at most 100) and `cursor` or `page`. In start_time order, ties are
+    // broken by id and one extra row is read to tell whether another page follows.
+    pub fn sort_and_page(mut self, sortable: &'static [&'static str], params: &QueryParams) -> Result<Self, QueryError> {
+        let column = match &params.sort_by {
+            Some(column) => Some(*sortable.iter().find(|c| **c == column.as_str()).ok_or_else(|| QueryError::UnknownSortColumn {
+                column: column.clone(),
+                allowed: sortable,
+            })?),
+            None => None,
+        };
+        if let Some(column) = column.filter(|c| *c != "start_time") {
+            if params.cursor.is_some() {
+                return Err(QueryError::CursorWithSort(column.to_string()));
+            }
+            self.order_by = Some(format!("{} {}", column, params.order.unwrap_or_default().as_sql()));
+            self.page_by_number(params);
+            return Ok(self);
+        }
+        let limit = params.limit.unwrap_or(10).clamp(1, 100);
+        let page = i64::from(params.page.unwrap_or(0));
+        let offset = page * limit;
+        let filters = (self.conditions.len(), self.params.len());
+
+        if params.cursor.is_some() && params.page.is_some() {
+            return Err(QueryError::CursorWithPage);
+        }
+        let descending = column.is_none() || params.order.unwrap_or_default() == SortOrder::Desc;
+        // The page before a cursor is read backwards from it and flipped in `paginate`.
+        let reversed = matches!(params.cursor, Some(Cursor { direction: CursorDirection::Prev, .. }));
+        let direction = if descending != reversed { "DESC" } else { "ASC" };
+        if let Some(cursor) = params.cursor {
+            let start_time = self.bind(cursor.start_time);
+            let id = self.bind(cursor.id);
+            let op = if direction == "DESC" { "<" } else { ">" };
+            self.conditions.push(format!("(start_time, id) {} ({}, {})", op, start_time, id));
+        }
+        self.order_by = Some(format!("start_time {0}, id {0}", direction));
+        let (page, offset) = if params.cursor.is_some() { (None, 0) } else { (Some(page), offset) };
+        let keyset = Keyset { reversed, has_prev: params.cursor.is_some() || offset > 0 };
+        self.paging = Some(Paging { limit, page, offset, filters, keyset: Some(keyset) });
+        let limit = self.bind(limit + 1);
+        let offset = self.bind(offset);
+        self.page = Some((limit, offset));
+        Ok(self)
+    }
+
+    // Groups rows by `group_by` and pages the groups by number, ordered by those columns with the
+    // first newest-first unless `order=asc`. Groups have no id to build cursors from, and `sort_by`
+    // may only name the first column.
+    pub fn group_and_page(mut self, group_by: &[&str], params: &QueryParams) -> Result<Self, QueryError> {
+        if params.cursor.is_some() {
+            return Err(QueryError::CursorUnsupported("aggregates".to_string()));
+        }
+        if let Some(column) = params.sort_by.as_ref().filter(|column| group_by.first() != Some(&column.as_str())) {
+            return Err(QueryError::BucketSort(column.clone()));
+        }
+        let direction = params.order.unwrap_or(SortOrder::Desc).as_sql();
+        self.group_by = Some(group_by.join(", "));
+        self.order_by = Some(group_by.iter().enumerate()
+            .map(|(i, column)| if i == 0 { format!("{} {}", column, direction) } else { column.to_string() })
+            .collect::<Vec<_>>()
+            .join(", "));
+        self.page_by_number(params);
+        Ok(self)
+    }
+
+    // `limit` (10 rows by default, at most 100) and `page` as LIMIT/OFFSET.
+    fn page_by_number(&mut self, params: &QueryParams) {
+        let limit = params.limit.unwrap_or(10).clamp(1, 100);
+        let page = i64::from(params.page.unwrap_or(0));
+        let offset = page * limit;
+        let filters = (self.conditions.len(), self.params.len());
+        self.paging = Some(Paging { limit, page: Some(page), offset, filters, keyset: None });
+        let limit = self.bind(limit);
+        let offset = self.bind(offset);
+        self.page = Some((limit, offset));
+    }
+
+    // Turns the rows of this query into a page of `total_count` matching rows, with `next`/`prev`
+    // cursors when it was read in start_time order and there is something on that side.
+    pub fn paginate<T: From<Row>>(&self, mut rows: Vec<Row>, total_count: i64) -> Page<T> {
+        let paging = self.paging.as_ref().expect("paginate needs sort_and_page");
+        let (mut next, mut prev) = (None, None);
+        let has_more = match &paging.keyset {
+            Some(keyset) => {
+                let more = rows.len() as i64 > paging.limit;
+                rows.truncate(paging.limit as usize);
+                if keyset.reversed {
+                    rows.reverse();
+                }
+                let (has_next, has_prev) = if keyset.reversed { (true, more) } else { (more, keyset.has_prev) };
+                let cursor = |row: &Row, direction| Cursor { direction, start_time: row.get("start_time"), id: row.get("id") }.encode();
+                next = rows.last().filter(|_| has_next).map(|row| cursor(row, CursorDirection::Next));
+                prev = rows.first().filter(|_| has_prev).map(|row| cursor(row, CursorDirection::Prev));
+                next.is_some()
+            }
+            None => paging.offset + (rows.len() as i64) < total_count,
+        };
+        Page {
+            items: rows.into_iter().map(T::from).collect(),
+            page: paging.page,
+            limit: paging.limit,
+            total_count,
+            has_more,
+            next,
+            prev,
+        }
+    }
+
+    // Counts every row the filters match, ignoring the cursor and the page.
+    pub fn count_sql(&self) -> String {
+        let conditions = self.paging.as_ref().map_or(self.conditions.len(), |p| p.filters.0);
+        let mut sql = format!("SELECT COUNT(*) FROM ({}", self.select);
+        if conditions > 0 {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions[..conditions].join(" AND "));
+        }
+        if let Some(group_by) = &self.group_by {
+            sql.push_str(&format!(" GROUP BY {}", group_by));
+        }
+        sql.push_str(") counted");
+        sql
+    }
+
+    pub fn count_params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        let params = self.paging.as_ref().map_or(self.params.len(), |p| p.filters.1);
+        self.params().into_iter().take(params).collect()
+    }
+
+    pub fn sql(&self) -> String {
+        let mut sql = self.select.clone();
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions.join(" AND "));
+        }
+        if let Some(group_by) = &self.group_by {
+            sql.push_str(&format!(" GROUP BY {}", group_by));
+        }
+        if let Some(order_by) = &self.order_by {
+            sql.push_str(&format!(" ORDER BY {}", order_by));
+        }
+        if let Some((limit, offset)) = &self.page {
+            sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
+        }
+        sql
+    }
+
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.params.iter().map(|p| p.as_ref() as &(dyn ToSql + Sync)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AggregateParams, FieldFilter};
+    use actix_web::web::Query;
+
+    fn params(query: &str) -> QueryParams {
+        let mut params = Query::<QueryParams>::from_query(query).unwrap().into_inner();
+        params.filters = FieldFilter::parse_query(query).unwrap();
+        params
+    }
+
+    #[test]
+    fn binds_every_request_value() {
+        let params = params("interval=hour&start_date=2023-08-01T00:00:00Z&sort_by=total_volume&order=desc&limit=5&page=2");
+        let query = QueryBuilder::new("SELECT * FROM swaps_history")
+            .history_filters("", &params)
+            .sort_and_page(&["start_time", "total_volume"], &params)
+            .unwrap();
+
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM swaps_history WHERE interval = $1 AND start_time >= $2 ORDER BY total_volume DESC LIMIT $3 OFFSET $4"
+        );
+        assert_eq!(query.params().len(), 4);
+    }
+
+    #[test]
+    fn filters_pools_only_where_data_has_pools() {
+        let params = params("pool=BTC.BTC&pools=ETH.ETH,%20DOGE.DOGE");
+        assert_eq!(params.requested_pools(), vec!["BTC.BTC", "ETH.ETH", "DOGE.DOGE"]);
+
+        let query = QueryBuilder::new("SELECT * FROM depth_price_history").pool_filter(Some("pool"), "depth history", &params).unwrap();
+        assert_eq!(query.sql(), "SELECT * FROM depth_price_history WHERE pool = ANY($1)");
+
+        let result = QueryBuilder::new("SELECT * FROM rune_pool_history").pool_filter(None, "runepool history", &params);
+        assert!(matches!(result, Err(QueryError::PoolFilterUnsupported(_))));
+    }
+
+    #[test]
+    fn binds_field_filters_on_numeric_columns() {
+        let params = params("total_volume_usd[gte]=1000000&average_slip[lt]=10&members_count%5Bbetween%5D=100,500&liquidity_gt=5");
+        let query = QueryBuilder::new("SELECT * FROM t")
+            .field_filters(&["start_time", "total_volume_usd", "average_slip", "members_count", "asset_depth"], &params)
+            .unwrap();
+
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM t WHERE total_volume_usd >= $1::NUMERIC AND average_slip < $2::NUMERIC \
+             AND members_count BETWEEN $3::NUMERIC AND $4::NUMERIC AND asset_depth > $5"
+        );
+        assert_eq!(query.params().len(), 5);
+    }
+
+    #[test]
+    fn rejects_malformed_or_unknown_field_filters() {
+        assert!(FieldFilter::parse_query("average_slip[like]=1").is_err());
+        assert!(FieldFilter::parse_query("average_slip[lt]=1;DROP").is_err());
+        assert!(FieldFilter::parse_query("members_count[between]=100").is_err());
+
+        let result = QueryBuilder::new("SELECT * FROM t").field_filters(&["start_time", "units"], &params("start_time[gt]=0"));
+        assert!(matches!(result, Err(QueryError::UnknownFilterColumn { .. })));
+        let result = QueryBuilder::new("SELECT * FROM t").field_filters(&["units"], &params("units%3B%20DROP%20TABLE%20t[gt]=0"));
+        assert!(matches!(result, Err(QueryError::UnknownFilterColumn { .. })));
+    }
+
+    #[test]
+    fn pages_by_cursor_instead_of_offset() {
+        let cursor = Cursor { direction: CursorDirection::Prev, start_time: "2023-11-15T00:00:00.5Z".parse().unwrap(), id: 42 };
+        assert_eq!(cursor.encode().parse::<Cursor>(), Ok(cursor));
+        assert!("bm90IGEgY3Vyc29y".parse::<Cursor>().is_err());
+
+        let query = QueryBuilder::new("SELECT * FROM t").sort_and_page(&["start_time"], &params(&format!("cursor={}&limit=2", cursor.encode()))).unwrap();
+        assert_eq!(query.sql(), "SELECT * FROM t WHERE (start_time, id) > ($1, $2) ORDER BY start_time ASC, id ASC LIMIT $3 OFFSET $4");
+
+        let with_sort = QueryBuilder::new("SELECT * FROM t").sort_and_page(&["units"], &params(&format!("cursor={}&sort_by=units", cursor.encode())));
+        assert!(matches!(with_sort, Err(QueryError::CursorWithSort(_))));
+        let with_page = QueryBuilder::new("SELECT * FROM t").sort_and_page(&["units"], &params(&format!("cursor={}&page=1", cursor.encode())));
+        assert!(matches!(with_page, Err(QueryError::CursorWithPage)));
+    }
+
+    struct Key(i32);
+
+    impl From<Row> for Key {
+        fn from(row: Row) -> Self {
+            Key(row.get("id"))
+        }
+    }
+
+    // Walks a table whose rows share start_times forwards and back again with limit=2.
+    #[actix_rt::test]
+    async fn cursors_walk_every_row_once_in_both_directions() {
+        let client = crate::db::test_pool().get().await.unwrap();
+        client.batch_execute(
+            "CREATE TEMP TABLE keyset_test (id INTEGER, start_time TIMESTAMPTZ);
+             INSERT INTO keyset_test VALUES (1, '2023-01-01'), (2, '2023-01-02'), (3, '2023-01-02'), (4, '2023-01-02'), (5, '2023-01-03');",
+        ).await.unwrap();
+        let fetch = |query: String| {
+            let client = &client;
+            async move {
+                let query = QueryBuilder::new("SELECT * FROM keyset_test").sort_and_page(&["start_time"], &params(&query)).unwrap();
+                let rows = client.query(&query.sql(), &query.params()).await.unwrap();
+                let total: i64 = client.query_one(&query.count_sql(), &query.count_params()).await.unwrap().get(0);
+                assert_eq!(total, 5);
+                let page = query.paginate::<Key>(rows, total);
+                (page.items.iter().map(|k| k.0).collect::<Vec<_>>(), page.next, page.prev)
+            }
+        };
+
+        let (ids, next, prev) = fetch("limit=2".to_string()).await;
+        assert_eq!((ids, prev), (vec![5, 4], None));
+        let (ids, next, _) = fetch(format!("limit=2&cursor={}", next.unwrap())).await;
+        assert_eq!(ids, vec![3, 2]);
+        let (ids, last, prev) = fetch(format!("limit=2&cursor={}", next.unwrap())).await;
+        assert_eq!((ids, last), (vec![1], None));
+        let (ids, _, prev) = fetch(format!("limit=2&cursor={}", prev.unwrap())).await;
+        assert_eq!(ids, vec![3, 2]);
+        let (ids, _, first) = fetch(format!("limit=2&cursor={}", prev.unwrap())).await;
+        assert_eq!((ids, first), (vec![5, 4], None));
+    }
+
+    #[test]
+    fn aggregates_whitelisted_fields_per_bucket() {
+        let aggregate = |query: &str| Query::<AggregateParams>::from_query(query).unwrap().into_inner();
+        let select = aggregate_select("swaps_history", &["start_time", "total_volume_usd", "total_fees"], true, &aggregate("bucket=month&agg=last&fields=total_volume_usd,total_fees")).unwrap();
+        let query = QueryBuilder::new(select).history_filters("", &params("")).group_and_page(&["bucket_start", "pool"], &params("order=asc")).unwrap();
+
+        assert_eq!(
+            query.sql(),
+            "SELECT date_trunc('month', start_time AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket_start, \
+             date_trunc('month', start_time AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' + INTERVAL '1 month' AS bucket_end, \
+             COUNT(*) AS row_count, BOOL_OR(is_partial) AS is_partial, pool, \
+             ((ARRAY_AGG(total_volume_usd ORDER BY start_time DESC))[1])::NUMERIC AS total_volume_usd, \
+             ((ARRAY_AGG(total_fees ORDER BY start_time DESC))[1])::NUMERIC AS total_fees \
+             FROM swaps_history WHERE interval = $1 GROUP BY bucket_start, pool ORDER BY bucket_start ASC, pool LIMIT $2 OFFSET $3"
+        );
+
+        let sorted = QueryBuilder::new("SELECT 1 FROM t").group_and_page(&["bucket_start", "pool"], &params("sort_by=bucket_start&order=asc"));
+        assert!(sorted.is_ok());
+        let sorted = QueryBuilder::new("SELECT 1 FROM t").group_and_page(&["bucket_start", "pool"], &params("sort_by=total_fees"));
+        assert!(matches!(sorted, Err(QueryError::BucketSort(_))));
+
+        let unknown = aggregate_select("swaps_history", &["start_time", "total_fees"], true, &aggregate("fields=total_fees,start_time"));
+        assert!(matches!(unknown, Err(QueryError::UnknownAggregateField { .. })));
+        let empty = aggregate_select("swaps_history", &["total_fees"], true, &aggregate("agg=avg"));
+        assert!(matches!(empty, Err(QueryError::NoAggregateFields { .. })));
+        assert!(Query::<AggregateParams>::from_query("fields=total_fees&agg=median").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_sort_order() {
+        assert!(Query::<QueryParams>::from_query("order=asc%3B%20DROP%20TABLE%20swaps_history").is_err());
+    }
+
+    #[test]
+    fn rejects_sort_column_outside_whitelist() {
+        let result = QueryBuilder::new("SELECT * FROM swaps_history")
+            .sort_and_page(&["start_time"], &params("sort_by=start_time%3B%20DROP%20TABLE%20swaps_history"));
+
+        assert!(matches!(result, Err(QueryError::UnknownSortColumn { .. })));
+    }
+}
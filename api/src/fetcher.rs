@@ -1,230 +1,810 @@
-use reqwest::Client;
-use deadpool_postgres::Pool;
-use crate::models::{DepthPrice, Swap, Earnings, PoolEarning, RunePool};
+use deadpool_postgres::{Pool, Transaction};
+use tokio_postgres::Row;
+use tokio_postgres::types::ToSql;
+use crate::midgard::{count_attempts, MidgardClient, MAX_INTERVALS};
+use crate::models::{DepthPrice, Swap, Earnings, PoolEarning, RunePool, Interval};
 use chrono::{DateTime, Utc};
-use std::time::Duration;
-use tokio::time::sleep;
-
-pub async fn fetch_depth_data(pool: &Pool, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "https://midgard.ninerealms.com/v2/history/depths/BTC.BTC?interval=day&count=400";
-    let mut attempts = 0;
-    let max_attempts = 3;
-    let response = loop {
-        let resp = client.get(url).send().await?;
-        if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            attempts += 1;
-            if attempts >= max_attempts {
-                return Err(format!("Failed to fetch depth data after {} attempts", max_attempts).into());
-            }
-            sleep(Duration::from_secs(2u64.pow(attempts as u32))).await;
-        } else if !resp.status().is_success() {
-            return Err(format!("Failed to fetch depth data: HTTP {}", resp.status()).into());
-        } else {
-            break resp;
+use futures::future::LocalBoxFuture;
+use log::{error, info};
+use std::collections::HashMap;
+use std::future::Future;
+
+// Postgres accepts at most 65535 bind parameters per statement.
+const MAX_BIND_PARAMS: usize = 65535;
+
+// One row's values, in column order, for a batched insert.
+type Params<'a> = Vec<&'a (dyn ToSql + Sync)>;
+
+// Rows a batched upsert added versus overwrote.
+#[derive(Debug, Default, Clone, Copy)]
+struct Upserted {
+    inserted: i64,
+    updated: i64,
+}
+
+impl Upserted {
+    // Expects each row to carry the `inserted` flag `insert_batch` returns.
+    fn count(rows: &[Row]) -> Self {
+        let inserted = rows.iter().filter(|row| row.get::<_, bool>("inserted")).count() as i64;
+        Self { inserted, updated: rows.len() as i64 - inserted }
+    }
+}
+
+// What a single history request stored, used to drive backfill paging.
+struct Window {
+    intervals: usize,
+    earliest_start: Option<DateTime<Utc>>,
+    latest_end: Option<DateTime<Utc>>,
+    reached_genesis: bool,
+    upserted: Upserted,
+}
+
+impl Window {
+    fn new(intervals: usize) -> Self {
+        Self { intervals, earliest_start: None, latest_end: None, reached_genesis: false, upserted: Upserted::default() }
+    }
+
+    fn record(&mut self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) {
+        if self.earliest_start.is_none_or(|earliest| start_time < earliest) {
+            self.earliest_start = Some(start_time);
+        }
+        if self.latest_end.is_none_or(|latest| end_time > latest) {
+            self.latest_end = Some(end_time);
         }
+    }
+}
+
+pub async fn fetch_pools(client: &dyn MidgardClient) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let pools = client.pools().await?;
+    Ok(pools.into_iter().map(|p| p.asset).collect())
+}
+
+// `fetch_pools` for the per-pool datasets. No per-pool run is logged when discovery fails, so the
+// failure is logged to ingestion_runs as a run of each of `datasets` without a pool.
+async fn discover_pools(pool: &Pool, client: &dyn MidgardClient, datasets: &[&str], granularity: Interval) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let started_at = Utc::now();
+    let (result, attempts) = count_attempts(fetch_pools(client)).await;
+    // Only the message is kept past this point, so the job future stays Send.
+    let error = match result.map_err(|e| e.to_string()) {
+        Ok(assets) => return Ok(assets),
+        Err(error) => error,
     };
-    let json: serde_json::Value = response.json().await?;
-    let intervals = json["intervals"].as_array().ok_or("Expected 'intervals' array")?;
     let db_client = pool.get().await?;
-    for interval in intervals {
-        let depth = DepthPrice {
-            id: 0, // Assigned by DB
-            pool: "BTC.BTC".to_string(),
-            start_time: DateTime::from_timestamp(interval["startTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            end_time: DateTime::from_timestamp(interval["endTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            asset_depth: interval["assetDepth"].as_str().unwrap_or("0").parse()?,
-            rune_depth: interval["runeDepth"].as_str().unwrap_or("0").parse()?,
-            asset_price: interval["assetPrice"].as_str().unwrap_or("0").parse()?,
-            asset_price_usd: interval["assetPriceUSD"].as_str().unwrap_or("0").parse()?,
-            liquidity_units: interval["liquidityUnits"].as_str().unwrap_or("0").parse()?,
-            members_count: interval["membersCount"].as_str().unwrap_or("0").parse()?,
-            synth_units: interval["synthUnits"].as_str().unwrap_or("0").parse()?,
-            synth_supply: interval["synthSupply"].as_str().unwrap_or("0").parse()?,
-            units: interval["units"].as_str().unwrap_or("0").parse()?,
-            luvi: interval["luvi"].as_str().unwrap_or("0").parse()?,
-        };
+    for dataset in datasets {
         db_client.execute(
-            "INSERT INTO depth_price_history (pool, start_time, end_time, asset_depth, rune_depth, asset_price, asset_price_usd, liquidity_units, members_count, synth_units, synth_supply, units, luvi)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) ON CONFLICT (pool, start_time, end_time) DO NOTHING",
-            &[&depth.pool, &depth.start_time, &depth.end_time, &depth.asset_depth, &depth.rune_depth, &depth.asset_price, &depth.asset_price_usd, &depth.liquidity_units, &depth.members_count, &depth.synth_units, &depth.synth_supply, &depth.units, &depth.luvi],
+            "INSERT INTO ingestion_runs (dataset, interval, started_at, finished_at, http_attempts, error) VALUES ($1, $2, $3, NOW(), $4, $5)",
+            &[dataset, &granularity.as_str(), &started_at, &(attempts as i32), &error],
         ).await?;
     }
+    Err(error.into())
+}
+
+pub async fn fetch_depth_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval) -> Result<(), Box<dyn std::error::Error>> {
+    let assets = discover_pools(pool, client, &["depth"], granularity).await?;
+    let mut failed = Vec::new();
+    for asset in assets {
+        let run = record_run(pool, "depth", Some(&asset), granularity, async {
+            let latest = resume_from(pool, "depth_price_history", granularity, Some(&asset)).await?;
+            Ok(fetch_pool_depth_window(pool, client, granularity, &asset, since(latest)).await?.upserted)
+        }).await;
+        if let Err(e) = run {
+            error!("Fetching {} depths for {} failed: {}", granularity, asset, e);
+            failed.push(asset);
+        }
+    }
+    pools_failed("depth", granularity, &failed)
+}
+
+pub async fn backfill_depth_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+    let assets = discover_pools(pool, client, &["depth"], granularity).await?;
+    for asset in assets {
+        let to = earliest_start_time(pool, "depth_price_history", granularity, Some(&asset)).await?;
+        walk_backwards(&format!("{} depths {}", granularity, asset), to, start, |range| {
+            Box::pin(fetch_pool_depth_window(pool, client, granularity, &asset, range))
+        }).await?;
+    }
     Ok(())
 }
 
-pub async fn fetch_swaps_data(pool: &Pool, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "https://midgard.ninerealms.com/v2/history/swaps?interval=day&count=400";
-    let mut attempts = 0;
-    let max_attempts = 3;
-    let response = loop {
-        let resp = client.get(url).send().await?;
-        if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            attempts += 1;
-            if attempts >= max_attempts {
-                return Err(format!("Failed to fetch swaps data after {} attempts", max_attempts).into());
-            }
-            sleep(Duration::from_secs(2u64.pow(attempts as u32))).await;
-        } else if !resp.status().is_success() {
-            return Err(format!("Failed to fetch swaps data: HTTP {}", resp.status()).into());
-        } else {
-            break resp;
+async fn fetch_pool_depth_window(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, asset: &str, range: String) -> Result<Window, Box<dyn std::error::Error>> {
+    let (depths, reached_genesis) = fetch_pool_depths(client, granularity, asset, range).await?;
+    let mut window = Window::new(depths.len());
+    depths.iter().for_each(|depth| window.record(depth.start_time, depth.end_time));
+    window.reached_genesis = reached_genesis;
+    window.upserted = store_depths(pool, &depths).await?;
+    Ok(window)
+}
+
+// Decoded depth intervals for one pool, and whether the window reaches back to the pool's genesis.
+async fn fetch_pool_depths(client: &dyn MidgardClient, granularity: Interval, asset: &str, range: String) -> Result<(Vec<DepthPrice>, bool), Box<dyn std::error::Error>> {
+    let history = client.depth_history(asset, granularity, &range).await?;
+    let depths = history.intervals.iter()
+        .map(|item| Ok(DepthPrice {
+            pool: asset.to_string(),
+            interval: granularity.to_string(),
+            ..DepthPrice::try_from(item)?
+        }))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+    // A window reaching back before the pool was created starts from empty depths.
+    let reached_genesis = history.meta.start_asset_depth == "0" && history.meta.start_rune_depth == "0";
+    Ok((depths, reached_genesis))
+}
+
+async fn store_depths(pool: &Pool, depths: &[DepthPrice]) -> Result<Upserted, Box<dyn std::error::Error>> {
+    let rows: Vec<Params> = depths.iter()
+        .map(|d| -> Params { vec![&d.pool, &d.interval, &d.start_time, &d.end_time, &d.is_partial, &d.asset_depth, &d.rune_depth, &d.asset_price, &d.asset_price_usd, &d.liquidity_units, &d.members_count, &d.synth_units, &d.synth_supply, &d.units, &d.luvi] })
+        .collect();
+    let mut db_client = pool.get().await?;
+    let tx = db_client.transaction().await?;
+    let upserted = insert_batch(
+        &tx,
+        "depth_price_history",
+        &["pool", "interval", "start_time", "end_time", "is_partial", "asset_depth", "rune_depth", "asset_price", "asset_price_usd", "liquidity_units", "members_count", "synth_units", "synth_supply", "units", "luvi"],
+        &["pool", "interval", "start_time", "end_time"],
+        &[],
+        &rows,
+    ).await?;
+    tx.commit().await?;
+    Ok(Upserted::count(&upserted))
+}
+
+pub async fn fetch_swaps_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval) -> Result<(), Box<dyn std::error::Error>> {
+    let assets = discover_pools(pool, client, &["swaps"], granularity).await?;
+    let mut failed = Vec::new();
+    for asset in assets {
+        let run = record_run(pool, "swaps", Some(&asset), granularity, async {
+            let latest = resume_from(pool, "swaps_history", granularity, Some(&asset)).await?;
+            Ok(fetch_pool_swaps_window(pool, client, granularity, &asset, since(latest)).await?.upserted)
+        }).await;
+        if let Err(e) = run {
+            error!("Fetching {} swaps for {} failed: {}", granularity, asset, e);
+            failed.push(asset);
         }
-    };
-    let json: serde_json::Value = response.json().await?;
-    let intervals = json["intervals"].as_array().ok_or("Expected 'intervals' array")?;
-    let db_client = pool.get().await?;
-    for interval in intervals {
-        let swap = Swap {
-            id: 0, // Assigned by DB
-            pool: "BTC.BTC".to_string(),
-            start_time: DateTime::from_timestamp(interval["startTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            end_time: DateTime::from_timestamp(interval["endTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            to_asset_count: interval["toAssetCount"].as_str().unwrap_or("0").parse()?,
-            to_rune_count: interval["toRuneCount"].as_str().unwrap_or("0").parse()?,
-            to_trade_count: interval["toTradeCount"].as_str().unwrap_or("0").parse()?,
-            from_trade_count: interval["fromTradeCount"].as_str().unwrap_or("0").parse()?,
-            synth_mint_count: interval["synthMintCount"].as_str().unwrap_or("0").parse()?,
-            synth_redeem_count: interval["synthRedeemCount"].as_str().unwrap_or("0").parse()?,
-            total_count: interval["totalCount"].as_str().unwrap_or("0").parse()?,
-            to_asset_volume: interval["toAssetVolume"].as_str().unwrap_or("0").parse()?,
-            to_rune_volume: interval["toRuneVolume"].as_str().unwrap_or("0").parse()?,
-            to_trade_volume: interval["toTradeVolume"].as_str().unwrap_or("0").parse()?,
-            from_trade_volume: interval["fromTradeVolume"].as_str().unwrap_or("0").parse()?,
-            synth_mint_volume: interval["synthMintVolume"].as_str().unwrap_or("0").parse()?,
-            synth_redeem_volume: interval["synthRedeemVolume"].as_str().unwrap_or("0").parse()?,
-            total_volume: interval["totalVolume"].as_str().unwrap_or("0").parse()?,
-            to_asset_volume_usd: interval["toAssetVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            to_rune_volume_usd: interval["toRuneVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            to_trade_volume_usd: interval["toTradeVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            from_trade_volume_usd: interval["fromTradeVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            synth_mint_volume_usd: interval["synthMintVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            synth_redeem_volume_usd: interval["synthRedeemVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            total_volume_usd: interval["totalVolumeUSD"].as_str().unwrap_or("0").parse()?,
-            to_asset_fees: interval["toAssetFees"].as_str().unwrap_or("0").parse()?,
-            to_rune_fees: interval["toRuneFees"].as_str().unwrap_or("0").parse()?,
-            to_trade_fees: interval["toTradeFees"].as_str().unwrap_or("0").parse()?,
-            from_trade_fees: interval["fromTradeFees"].as_str().unwrap_or("0").parse()?,
-            synth_mint_fees: interval["synthMintFees"].as_str().unwrap_or("0").parse()?,
-            synth_redeem_fees: interval["synthRedeemFees"].as_str().unwrap_or("0").parse()?,
-            total_fees: interval["totalFees"].as_str().unwrap_or("0").parse()?,
-            to_asset_average_slip: interval["toAssetAverageSlip"].as_str().unwrap_or("0").parse()?,
-            to_rune_average_slip: interval["toRuneAverageSlip"].as_str().unwrap_or("0").parse()?,
-            to_trade_average_slip: interval["toTradeAverageSlip"].as_str().unwrap_or("0").parse()?,
-            from_trade_average_slip: interval["fromTradeAverageSlip"].as_str().unwrap_or("0").parse()?,
-            synth_mint_average_slip: interval["synthMintAverageSlip"].as_str().unwrap_or("0").parse()?,
-            synth_redeem_average_slip: interval["synthRedeemAverageSlip"].as_str().unwrap_or("0").parse()?,
-            average_slip: interval["averageSlip"].as_str().unwrap_or("0").parse()?,
-            rune_price_usd: interval["runePriceUSD"].as_str().unwrap_or("0").parse()?,
-        };
-        db_client.execute(
-            "INSERT INTO swaps_history (pool, start_time, end_time, to_asset_count, to_rune_count, to_trade_count, from_trade_count, synth_mint_count, synth_redeem_count, total_count, to_asset_volume, to_rune_volume, to_trade_volume, from_trade_volume, synth_mint_volume, synth_redeem_volume, total_volume, to_asset_volume_usd, to_rune_volume_usd, to_trade_volume_usd, from_trade_volume_usd, synth_mint_volume_usd, synth_redeem_volume_usd, total_volume_usd, to_asset_fees, to_rune_fees, to_trade_fees, from_trade_fees, synth_mint_fees, synth_redeem_fees, total_fees, to_asset_average_slip, to_rune_average_slip, to_trade_average_slip, from_trade_average_slip, synth_mint_average_slip, synth_redeem_average_slip, average_slip, rune_price_usd)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39)
-             ON CONFLICT (pool, start_time, end_time) DO NOTHING",
-            &[&swap.pool, &swap.start_time, &swap.end_time, &swap.to_asset_count, &swap.to_rune_count, &swap.to_trade_count, &swap.from_trade_count, &swap.synth_mint_count, &swap.synth_redeem_count, &swap.total_count, &swap.to_asset_volume, &swap.to_rune_volume, &swap.to_trade_volume, &swap.from_trade_volume, &swap.synth_mint_volume, &swap.synth_redeem_volume, &swap.total_volume, &swap.to_asset_volume_usd, &swap.to_rune_volume_usd, &swap.to_trade_volume_usd, &swap.from_trade_volume_usd, &swap.synth_mint_volume_usd, &swap.synth_redeem_volume_usd, &swap.total_volume_usd, &swap.to_asset_fees, &swap.to_rune_fees, &swap.to_trade_fees, &swap.from_trade_fees, &swap.synth_mint_fees, &swap.synth_redeem_fees, &swap.total_fees, &swap.to_asset_average_slip, &swap.to_rune_average_slip, &swap.to_trade_average_slip, &swap.from_trade_average_slip, &swap.synth_mint_average_slip, &swap.synth_redeem_average_slip, &swap.average_slip, &swap.rune_price_usd],
-        ).await?;
+    }
+    pools_failed("swaps", granularity, &failed)
+}
+
+pub async fn backfill_swaps_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+    let assets = discover_pools(pool, client, &["swaps"], granularity).await?;
+    for asset in assets {
+        let to = earliest_start_time(pool, "swaps_history", granularity, Some(&asset)).await?;
+        walk_backwards(&format!("{} swaps {}", granularity, asset), to, start, |range| {
+            Box::pin(fetch_pool_swaps_window(pool, client, granularity, &asset, range))
+        }).await?;
     }
     Ok(())
 }
 
-pub async fn fetch_earnings_data(pool: &Pool, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "https://midgard.ninerealms.com/v2/history/earnings?interval=day&count=400";
-    let mut attempts = 0;
-    let max_attempts = 3;
-    let response = loop {
-        let resp = client.get(url).send().await?;
-        if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            attempts += 1;
-            if attempts >= max_attempts {
-                return Err(format!("Failed to fetch earnings data after {} attempts", max_attempts).into());
-            }
-            sleep(Duration::from_secs(2u64.pow(attempts as u32))).await;
-        } else if !resp.status().is_success() {
-            return Err(format!("Failed to fetch earnings data: HTTP {}", resp.status()).into());
-        } else {
-            break resp;
+async fn fetch_pool_swaps_window(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, asset: &str, range: String) -> Result<Window, Box<dyn std::error::Error>> {
+    let swaps = fetch_pool_swaps(client, granularity, asset, range).await?;
+    let mut window = Window::new(swaps.len());
+    swaps.iter().for_each(|swap| window.record(swap.start_time, swap.end_time));
+    window.upserted = store_swaps(pool, &swaps).await?;
+    Ok(window)
+}
+
+async fn fetch_pool_swaps(client: &dyn MidgardClient, granularity: Interval, asset: &str, range: String) -> Result<Vec<Swap>, Box<dyn std::error::Error>> {
+    let history = client.swap_history(asset, granularity, &range).await?;
+    history.intervals.iter()
+        .map(|item| Ok(Swap {
+            pool: asset.to_string(),
+            interval: granularity.to_string(),
+            ..Swap::try_from(item)?
+        }))
+        .collect()
+}
+
+async fn store_swaps(pool: &Pool, swaps: &[Swap]) -> Result<Upserted, Box<dyn std::error::Error>> {
+    let rows: Vec<Params> = swaps.iter()
+        .map(|s| -> Params { vec![&s.pool, &s.interval, &s.start_time, &s.end_time, &s.is_partial, &s.to_asset_count, &s.to_rune_count, &s.to_trade_count, &s.from_trade_count, &s.synth_mint_count, &s.synth_redeem_count, &s.total_count, &s.to_asset_volume, &s.to_rune_volume, &s.to_trade_volume, &s.from_trade_volume, &s.synth_mint_volume, &s.synth_redeem_volume, &s.total_volume, &s.to_asset_volume_usd, &s.to_rune_volume_usd, &s.to_trade_volume_usd, &s.from_trade_volume_usd, &s.synth_mint_volume_usd, &s.synth_redeem_volume_usd, &s.total_volume_usd, &s.to_asset_fees, &s.to_rune_fees, &s.to_trade_fees, &s.from_trade_fees, &s.synth_mint_fees, &s.synth_redeem_fees, &s.total_fees, &s.to_asset_average_slip, &s.to_rune_average_slip, &s.to_trade_average_slip, &s.from_trade_average_slip, &s.synth_mint_average_slip, &s.synth_redeem_average_slip, &s.average_slip, &s.rune_price_usd] })
+        .collect();
+    let mut db_client = pool.get().await?;
+    let tx = db_client.transaction().await?;
+    let upserted = insert_batch(
+        &tx,
+        "swaps_history",
+        &["pool", "interval", "start_time", "end_time", "is_partial", "to_asset_count", "to_rune_count", "to_trade_count", "from_trade_count", "synth_mint_count", "synth_redeem_count", "total_count", "to_asset_volume", "to_rune_volume", "to_trade_volume", "from_trade_volume", "synth_mint_volume", "synth_redeem_volume", "total_volume", "to_asset_volume_usd", "to_rune_volume_usd", "to_trade_volume_usd", "from_trade_volume_usd", "synth_mint_volume_usd", "synth_redeem_volume_usd", "total_volume_usd", "to_asset_fees", "to_rune_fees", "to_trade_fees", "from_trade_fees", "synth_mint_fees", "synth_redeem_fees", "total_fees", "to_asset_average_slip", "to_rune_average_slip", "to_trade_average_slip", "from_trade_average_slip", "synth_mint_average_slip", "synth_redeem_average_slip", "average_slip", "rune_price_usd"],
+        &["pool", "interval", "start_time", "end_time"],
+        &[],
+        &rows,
+    ).await?;
+    tx.commit().await?;
+    Ok(Upserted::count(&upserted))
+}
+
+pub async fn fetch_earnings_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval) -> Result<(), Box<dyn std::error::Error>> {
+    record_run(pool, "earnings", None, granularity, async {
+        let latest = resume_from(pool, "earnings_history", granularity, None).await?;
+        Ok(fetch_earnings_window(pool, client, granularity, since(latest)).await?.upserted)
+    }).await
+}
+
+pub async fn backfill_earnings_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+    let to = earliest_start_time(pool, "earnings_history", granularity, None).await?;
+    walk_backwards(&format!("{} earnings", granularity), to, start, |range| Box::pin(fetch_earnings_window(pool, client, granularity, range))).await
+}
+
+async fn fetch_earnings_window(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, range: String) -> Result<Window, Box<dyn std::error::Error>> {
+    let history = client.earnings_history(granularity, &range).await?;
+    let mut window = Window::new(history.intervals.len());
+    let mut earnings = Vec::new();
+    let mut pool_earnings = Vec::new();
+    for item in &history.intervals {
+        let interval_earnings = Earnings {
+            interval: granularity.to_string(),
+            ..Earnings::try_from(item)?
+        };
+        window.record(interval_earnings.start_time, interval_earnings.end_time);
+        for pool_item in &item.pools {
+            pool_earnings.push((interval_earnings.start_time, PoolEarning::try_from((item, pool_item))?));
         }
+        earnings.push(interval_earnings);
+    }
+
+    let mut db_client = pool.get().await?;
+    let tx = db_client.transaction().await?;
+    let rows: Vec<Params> = earnings.iter()
+        .map(|e| -> Params { vec![&e.interval, &e.start_time, &e.end_time, &e.is_partial, &e.liquidity_fees, &e.block_rewards, &e.earnings, &e.bonding_earnings, &e.liquidity_earnings, &e.avg_node_count, &e.rune_price_usd] })
+        .collect();
+    let inserted = insert_batch(
+        &tx,
+        "earnings_history",
+        &["interval", "start_time", "end_time", "is_partial", "liquidity_fees", "block_rewards", "earnings", "bonding_earnings", "liquidity_earnings", "avg_node_count", "rune_price_usd"],
+        &["interval", "start_time", "end_time"],
+        &["id", "start_time"],
+        &rows,
+    ).await?;
+    // RETURNING gives no ordering guarantee, so children are matched to their parent by start_time.
+    window.upserted = Upserted::count(&inserted);
+    let ids: HashMap<DateTime<Utc>, i32> = inserted.iter().map(|row| (row.get("start_time"), row.get("id"))).collect();
+    for (start_time, pool_earning) in &mut pool_earnings {
+        pool_earning.earnings_history_id = ids[start_time];
+    }
+    let rows: Vec<Params> = pool_earnings.iter()
+        .map(|(_, p)| -> Params { vec![&p.earnings_history_id, &p.pool, &p.asset_liquidity_fees, &p.rune_liquidity_fees, &p.total_liquidity_fees_rune, &p.saver_earning, &p.rewards, &p.earnings] })
+        .collect();
+    insert_batch(
+        &tx,
+        "pool_earnings",
+        &["earnings_history_id", "pool", "asset_liquidity_fees", "rune_liquidity_fees", "total_liquidity_fees_rune", "saver_earning", "rewards", "earnings"],
+        &["earnings_history_id", "pool"],
+        &[],
+        &rows,
+    ).await?;
+    // Pools Midgard no longer lists for an interval would otherwise keep their old rows.
+    let history_ids: Vec<i32> = ids.values().copied().collect();
+    let (kept_ids, kept_pools): (Vec<i32>, Vec<&str>) = pool_earnings.iter().map(|(_, p)| (p.earnings_history_id, p.pool.as_str())).unzip();
+    tx.execute(
+        "DELETE FROM pool_earnings p WHERE p.earnings_history_id = ANY($1)
+         AND NOT EXISTS (SELECT 1 FROM unnest($2::INT[], $3::TEXT[]) k(id, pool) WHERE k.id = p.earnings_history_id AND k.pool = p.pool)",
+        &[&history_ids, &kept_ids, &kept_pools],
+    ).await?;
+    tx.commit().await?;
+    Ok(window)
+}
+
+pub async fn fetch_runepool_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval) -> Result<(), Box<dyn std::error::Error>> {
+    record_run(pool, "runepool", None, granularity, async {
+        let latest = resume_from(pool, "rune_pool_history", granularity, None).await?;
+        Ok(fetch_runepool_window(pool, client, granularity, since(latest)).await?.upserted)
+    }).await
+}
+
+pub async fn backfill_runepool_data(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+    let to = earliest_start_time(pool, "rune_pool_history", granularity, None).await?;
+    walk_backwards(&format!("{} runepool", granularity), to, start, |range| Box::pin(fetch_runepool_window(pool, client, granularity, range))).await
+}
+
+async fn fetch_runepool_window(pool: &Pool, client: &dyn MidgardClient, granularity: Interval, range: String) -> Result<Window, Box<dyn std::error::Error>> {
+    let history = client.runepool_history(granularity, &range).await?;
+    let mut window = Window::new(history.intervals.len());
+    let mut runepools = Vec::new();
+    for item in &history.intervals {
+        let runepool = RunePool {
+            interval: granularity.to_string(),
+            ..RunePool::try_from(item)?
+        };
+        window.record(runepool.start_time, runepool.end_time);
+        runepools.push(runepool);
+    }
+
+    let rows: Vec<Params> = runepools.iter()
+        .map(|r| -> Params { vec![&r.interval, &r.start_time, &r.end_time, &r.is_partial, &r.count, &r.units] })
+        .collect();
+    let mut db_client = pool.get().await?;
+    let tx = db_client.transaction().await?;
+    let upserted = insert_batch(
+        &tx,
+        "rune_pool_history",
+        &["interval", "start_time", "end_time", "is_partial", "count", "units"],
+        &["interval", "start_time", "end_time"],
+        &[],
+        &rows,
+    ).await?;
+    tx.commit().await?;
+    window.upserted = Upserted::count(&upserted);
+    Ok(window)
+}
+
+// Upserts `rows` as multi-row `INSERT ... VALUES (...), (...)` statements, split only where
+// Postgres' bind parameter limit requires it. A row already stored under the `key` columns is
+// overwritten with the new values, so revisions of an open interval replace what was seen
+// before. Returns one row per upserted row with an `inserted` flag (false when it overwrote an
+// existing row) and the `returning` columns.
+async fn insert_batch(tx: &Transaction<'_>, table: &str, columns: &[&str], key: &[&str], returning: &[&str], rows: &[Params<'_>]) -> Result<Vec<Row>, Box<dyn std::error::Error>> {
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let updates: Vec<String> = columns.iter()
+        .filter(|column| !key.contains(column))
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+    // xmax is only set on a row version that replaced an existing one.
+    let returning = std::iter::once("(xmax = 0) AS inserted").chain(returning.iter().copied()).collect::<Vec<_>>().join(", ");
+    let mut returned = Vec::new();
+    for chunk in rows.chunks(MAX_BIND_PARAMS / columns.len()) {
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES {} ON CONFLICT ({}) DO UPDATE SET {} RETURNING {}",
+            table, columns.join(", "), values_placeholders(chunk.len(), columns.len()), key.join(", "), updates.join(", "), returning,
+        );
+        let params: Params = chunk.iter().flatten().copied().collect();
+        returned.extend(tx.query(&sql, &params).await?);
+    }
+    Ok(returned)
+}
+
+// "($1, $2), ($3, $4)" for two rows of two columns.
+fn values_placeholders(rows: usize, width: usize) -> String {
+    (0..rows)
+        .map(|row| {
+            let columns: Vec<String> = (1..=width).map(|column| format!("${}", row * width + column)).collect();
+            format!("({})", columns.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Logs one ingestion run of `dataset` (per pool, where the dataset has pools) to ingestion_runs,
+// including the HTTP requests it took and its error, if any. The run's own result is returned.
+async fn record_run<F>(pool: &Pool, dataset: &str, asset: Option<&str>, granularity: Interval, run: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: Future<Output = Result<Upserted, Box<dyn std::error::Error>>>,
+{
+    let row = pool.get().await?.query_one(
+        "INSERT INTO ingestion_runs (dataset, pool, interval, started_at) VALUES ($1, $2, $3, NOW()) RETURNING id",
+        &[&dataset, &asset, &granularity.as_str()],
+    ).await?;
+    let id: i32 = row.get("id");
+
+    let (result, attempts) = count_attempts(run).await;
+    // Only the message is kept past this point, so the job future stays Send.
+    let result = result.map_err(|e| e.to_string());
+    let upserted = result.as_ref().copied().unwrap_or_default();
+    pool.get().await?.execute(
+        "UPDATE ingestion_runs SET finished_at = NOW(), rows_inserted = $2, rows_updated = $3, http_attempts = $4, error = $5 WHERE id = $1",
+        &[&id, &upserted.inserted, &upserted.updated, &(attempts as i32), &result.as_ref().err()],
+    ).await?;
+    result.map(|_| ()).map_err(Into::into)
+}
+
+// One pool failing does not hold up the rest, but the job still reports which ones did.
+fn pools_failed(dataset: &str, granularity: Interval, failed: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(format!("{} {} failed for {} pool(s): {}", granularity, dataset, failed.len(), failed.join(", ")).into())
+}
+
+// Where the incremental fetch picks up: the start of the oldest interval that was still open when
+// stored, so it gets revised, or else the end of the newest stored interval.
+async fn resume_from(pool: &Pool, table: &str, granularity: Interval, asset: Option<&str>) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let db_client = pool.get().await?;
+    let select = "SELECT COALESCE(MIN(start_time) FILTER (WHERE is_partial), MAX(end_time)) AS latest";
+    let row = match asset {
+        Some(asset) => db_client.query_one(&format!("{} FROM {} WHERE interval = $1 AND pool = $2", select, table), &[&granularity.as_str(), &asset]).await?,
+        None => db_client.query_one(&format!("{} FROM {} WHERE interval = $1", select, table), &[&granularity.as_str()]).await?,
     };
-    let json: serde_json::Value = response.json().await?;
-    let intervals = json["intervals"].as_array().ok_or("Expected 'intervals' array")?;
+    Ok(row.get("latest"))
+}
+
+// Query range for intervals after `latest`; an empty table falls back to the most recent window.
+// The still-open interval ends in the future, so the bound is clamped to now.
+fn since(latest: Option<DateTime<Utc>>) -> String {
+    latest
+        .map(|t| format!("&from={}", t.min(Utc::now()).timestamp()))
+        .unwrap_or_default()
+}
+
+// Start of the oldest stored interval, which is where an interrupted backfill resumes from.
+async fn earliest_start_time(pool: &Pool, table: &str, granularity: Interval, asset: Option<&str>) -> Result<Option<DateTime<Utc>>, Box<dyn std::error::Error>> {
     let db_client = pool.get().await?;
-    for interval in intervals {
-        let earnings = Earnings {
-            id: 0, // Assigned by DB
-            start_time: DateTime::from_timestamp(interval["startTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            end_time: DateTime::from_timestamp(interval["endTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            liquidity_fees: interval["liquidityFees"].as_str().unwrap_or("0").parse()?,
-            block_rewards: interval["blockRewards"].as_str().unwrap_or("0").parse()?,
-            earnings: interval["earnings"].as_str().unwrap_or("0").parse()?,
-            bonding_earnings: interval["bondingEarnings"].as_str().unwrap_or("0").parse()?,
-            liquidity_earnings: interval["liquidityEarnings"].as_str().unwrap_or("0").parse()?,
-            avg_node_count: interval["avgNodeCount"].as_str().unwrap_or("0").parse()?,
-            rune_price_usd: interval["runePriceUSD"].as_str().unwrap_or("0").parse()?,
+    let row = match asset {
+        Some(asset) => db_client.query_one(&format!("SELECT MIN(start_time) AS earliest FROM {} WHERE interval = $1 AND pool = $2", table), &[&granularity.as_str(), &asset]).await?,
+        None => db_client.query_one(&format!("SELECT MIN(start_time) AS earliest FROM {} WHERE interval = $1", table), &[&granularity.as_str()]).await?,
+    };
+    Ok(row.get("earliest"))
+}
+
+// Pages backwards through Midgard history in MAX_INTERVALS windows ending at `to`, until the
+// pool's genesis, the configured `start`, or a window that no longer moves further back.
+async fn walk_backwards<'a, F>(label: &str, mut to: Option<DateTime<Utc>>, start: Option<DateTime<Utc>>, mut fetch_window: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut(String) -> LocalBoxFuture<'a, Result<Window, Box<dyn std::error::Error>>>,
+{
+    loop {
+        let range = to.map(|t| format!("&to={}", t.timestamp())).unwrap_or_default();
+        let window = fetch_window(range).await?;
+        let Some(earliest) = window.earliest_start else {
+            break;
         };
-        let row = db_client.query_one(
-            "INSERT INTO earnings_history (start_time, end_time, liquidity_fees, block_rewards, earnings, bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (start_time, end_time) DO UPDATE SET liquidity_fees = EXCLUDED.liquidity_fees RETURNING id",
-            &[&earnings.start_time, &earnings.end_time, &earnings.liquidity_fees, &earnings.block_rewards, &earnings.earnings, &earnings.bonding_earnings, &earnings.liquidity_earnings, &earnings.avg_node_count, &earnings.rune_price_usd],
-        ).await?;
-        let earnings_id: i32 = row.get("id");
-
-        if let Some(pools) = interval["pools"].as_array() {
-            for pool_data in pools {
-                let pool_earning = PoolEarning {
-                    id: 0, // Assigned by DB
-                    earnings_history_id: earnings_id,
-                    pool: pool_data["pool"].as_str().unwrap_or("BTC.BTC").to_string(),
-                    asset_liquidity_fees: pool_data["assetLiquidityFees"].as_str().unwrap_or("0").parse()?,
-                    rune_liquidity_fees: pool_data["runeLiquidityFees"].as_str().unwrap_or("0").parse()?,
-                    total_liquidity_fees_rune: pool_data["totalLiquidityFeesRune"].as_str().unwrap_or("0").parse()?,
-                    saver_earning: pool_data["saverEarning"].as_str().unwrap_or("0").parse()?,
-                    rewards: pool_data["rewards"].as_str().unwrap_or("0").parse()?,
-                    earnings: pool_data["earnings"].as_str().unwrap_or("0").parse()?,
-                };
-                db_client.execute(
-                    "INSERT INTO pool_earnings (earnings_history_id, pool, asset_liquidity_fees, rune_liquidity_fees, total_liquidity_fees_rune, saver_earning, rewards, earnings)
-                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (earnings_history_id, pool) DO NOTHING",
-                    &[&pool_earning.earnings_history_id, &pool_earning.pool, &pool_earning.asset_liquidity_fees, &pool_earning.rune_liquidity_fees, &pool_earning.total_liquidity_fees_rune, &pool_earning.saver_earning, &pool_earning.rewards, &pool_earning.earnings],
-                ).await?;
-            }
+        info!("Backfilled {} intervals of {} back to {}", window.intervals, label, earliest);
+        if window.reached_genesis
+            || window.intervals < MAX_INTERVALS
+            || start.is_some_and(|start| earliest <= start)
+            || to.is_some_and(|to| earliest >= to)
+        {
+            break;
         }
+        to = Some(earliest);
     }
     Ok(())
 }
 
-pub async fn fetch_runepool_data(pool: &Pool, client: &Client) -> Result<(), Box<dyn std::error::Error>> {
-    let url = "https://midgard.ninerealms.com/v2/history/runepool?interval=day&count=400";
-    let mut attempts = 0;
-    let max_attempts = 3;
-    let response = loop {
-        let resp = client.get(url).send().await?;
-        if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
-            attempts += 1;
-            if attempts >= max_attempts {
-                return Err(format!("Failed to fetch runepool data after {} attempts", max_attempts).into());
-            }
-            sleep(Duration::from_secs(2u64.pow(attempts as u32))).await;
-        } else if !resp.status().is_success() {
-            return Err(format!("Failed to fetch runepool data: HTTP {}", resp.status()).into());
-        } else {
-            break resp;
+// A stored history series that gap repair knows how to re-fetch.
+#[derive(Debug, Clone, Copy)]
+enum Dataset {
+    Depth,
+    Swaps,
+    Earnings,
+    RunePool,
+}
+
+impl Dataset {
+    fn name(self) -> &'static str {
+        match self {
+            Dataset::Depth => "depth",
+            Dataset::Swaps => "swaps",
+            Dataset::Earnings => "earnings",
+            Dataset::RunePool => "runepool",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Dataset::Depth => "depth_price_history",
+            Dataset::Swaps => "swaps_history",
+            Dataset::Earnings => "earnings_history",
+            Dataset::RunePool => "rune_pool_history",
+        }
+    }
+
+    async fn fetch_window(self, pool: &Pool, client: &dyn MidgardClient, granularity: Interval, asset: Option<&str>, range: String) -> Result<Window, Box<dyn std::error::Error>> {
+        match (self, asset) {
+            (Dataset::Depth, Some(asset)) => fetch_pool_depth_window(pool, client, granularity, asset, range).await,
+            (Dataset::Swaps, Some(asset)) => fetch_pool_swaps_window(pool, client, granularity, asset, range).await,
+            (Dataset::Earnings, None) => fetch_earnings_window(pool, client, granularity, range).await,
+            (Dataset::RunePool, None) => fetch_runepool_window(pool, client, granularity, range).await,
+            _ => Err(format!("{} history is {}", self.name(), if asset.is_some() { "not per pool" } else { "per pool" }).into()),
+        }
+    }
+}
+
+// Finds missing intervals between stored ones in every dataset (and pool) at `granularity` and
+// re-fetches exactly those ranges. Gaps that are still there afterwards are recorded in
+// interval_gaps as unrecoverable; gaps that were filled are marked resolved. A series that fails
+// is logged and skipped, and reported once the others are done.
+pub async fn repair_gaps(pool: &Pool, client: &dyn MidgardClient, granularity: Interval) -> Result<(), Box<dyn std::error::Error>> {
+    let mut failed = Vec::new();
+    let assets = match discover_pools(pool, client, &["depth", "swaps"], granularity).await {
+        Ok(assets) => assets,
+        Err(e) => {
+            error!("Discovering pools for {} gap repair failed: {}", granularity, e);
+            failed.push("pool discovery".to_string());
+            Vec::new()
         }
     };
-    let json: serde_json::Value = response.json().await?;
-    let intervals = json["intervals"].as_array().ok_or("Expected 'intervals' array")?;
+    let series = assets.iter()
+        .flat_map(|asset| [(Dataset::Depth, Some(asset.as_str())), (Dataset::Swaps, Some(asset.as_str()))])
+        .chain([(Dataset::Earnings, None), (Dataset::RunePool, None)]);
+    for (dataset, asset) in series {
+        if let Err(e) = repair_dataset_gaps(pool, client, dataset, granularity, asset).await {
+            error!("Repairing {} {} {} gaps failed: {}", granularity, dataset.name(), asset.unwrap_or_default(), e);
+            failed.push(match asset {
+                Some(asset) => format!("{} {}", dataset.name(), asset),
+                None => dataset.name().to_string(),
+            });
+        }
+    }
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(format!("{} gap repair failed for {}", granularity, failed.join(", ")).into())
+}
+
+async fn repair_dataset_gaps(pool: &Pool, client: &dyn MidgardClient, dataset: Dataset, granularity: Interval, asset: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let gaps = find_gaps(pool, dataset, granularity, asset).await?;
+    // A failed fetch ends the repair of this series, but the gaps still open are recorded first.
+    let mut failure = None;
+    for &(gap_start, gap_end) in &gaps {
+        info!("Repairing {} {} {} gap from {} to {}", granularity, dataset.name(), asset.unwrap_or_default(), gap_start, gap_end);
+        // Midgard only accepts two of from, to and count, so the gap is paged forwards from its start.
+        let mut from = gap_start;
+        loop {
+            let window = match dataset.fetch_window(pool, client, granularity, asset, format!("&from={}", from.timestamp())).await {
+                Ok(window) => window,
+                Err(e) => {
+                    failure = Some(e.to_string());
+                    break;
+                }
+            };
+            match window.latest_end {
+                Some(end) if end > from && end < gap_end && window.intervals >= MAX_INTERVALS => from = end,
+                _ => break,
+            }
+        }
+        if failure.is_some() {
+            break;
+        }
+    }
+
+    let remaining = if gaps.is_empty() { gaps } else { find_gaps(pool, dataset, granularity, asset).await? };
     let db_client = pool.get().await?;
-    for interval in intervals {
-        let runepool = RunePool {
-            id: 0, // Assigned by DB
-            start_time: DateTime::from_timestamp(interval["startTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            end_time: DateTime::from_timestamp(interval["endTime"].as_str().unwrap_or("0").parse::<i64>()? / 1000, 0).unwrap_or(DateTime::<Utc>::MIN_UTC),
-            count: interval["memberCount"].as_str().unwrap_or("0").parse()?,
-            units: interval["units"].as_str().unwrap_or("0").parse()?,
-        };
+    for (gap_start, gap_end) in &remaining {
         db_client.execute(
-            "INSERT INTO rune_pool_history (start_time, end_time, count, units)
-             VALUES ($1, $2, $3, $4) ON CONFLICT (start_time, end_time) DO NOTHING",
-            &[&runepool.start_time, &runepool.end_time, &runepool.count, &runepool.units],
+            "INSERT INTO interval_gaps (dataset, pool, interval, gap_start, gap_end, detected_at, last_attempt_at, repair_attempts)
+             VALUES ($1, $2, $3, $4, $5, NOW(), NOW(), 1)
+             ON CONFLICT (dataset, (COALESCE(pool, '')), interval, gap_start)
+             DO UPDATE SET gap_end = EXCLUDED.gap_end, last_attempt_at = NOW(), repair_attempts = interval_gaps.repair_attempts + 1, resolved_at = NULL",
+            &[&dataset.name(), &asset, &granularity.as_str(), gap_start, gap_end],
         ).await?;
     }
-    Ok(())
-}
\ No newline at end of file
+    let open: Vec<DateTime<Utc>> = remaining.iter().map(|(gap_start, _)| *gap_start).collect();
+    db_client.execute(
+        "UPDATE interval_gaps SET resolved_at = NOW()
+         WHERE dataset = $1 AND pool IS NOT DISTINCT FROM $2 AND interval = $3 AND resolved_at IS NULL AND gap_start <> ALL($4)",
+        &[&dataset.name(), &asset, &granularity.as_str(), &open],
+    ).await?;
+    failure.map_or(Ok(()), |e| Err(e.into()))
+}
+
+// Ranges between a stored interval's end and the next stored interval's start. Midgard intervals
+// are contiguous, so any such range is missing data.
+async fn find_gaps(pool: &Pool, dataset: Dataset, granularity: Interval, asset: Option<&str>) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, Box<dyn std::error::Error>> {
+    let db_client = pool.get().await?;
+    let filter = if asset.is_some() { "interval = $1 AND pool = $2" } else { "interval = $1" };
+    let query = format!(
+        "SELECT gap_start, gap_end FROM (
+             SELECT end_time AS gap_start, LEAD(start_time) OVER (ORDER BY start_time) AS gap_end
+             FROM {} WHERE {}
+         ) buckets
+         WHERE gap_end > gap_start
+         ORDER BY gap_start",
+        dataset.table(), filter,
+    );
+    let rows = match asset {
+        Some(asset) => db_client.query(&query, &[&granularity.as_str(), &asset]).await?,
+        None => db_client.query(&query, &[&granularity.as_str()]).await?,
+    };
+    Ok(rows.iter().map(|row| (row.get("gap_start"), row.get("gap_end"))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{insert_test_depths, test_depth, test_pool, Database};
+    use crate::midgard::MidgardError;
+    use crate::midgard::fixture::FixtureMidgardClient;
+    use async_trait::async_trait;
+    use reqwest::StatusCode;
+
+    // Answers from the fixtures, except that every request about `pool` fails.
+    #[derive(Default)]
+    struct FailingPool {
+        fixtures: FixtureMidgardClient,
+        pool: &'static str,
+    }
+
+    #[async_trait]
+    impl MidgardClient for FailingPool {
+        async fn get_json(&self, path: &str) -> Result<serde_json::Value, MidgardError> {
+            if path.contains(self.pool) {
+                return Err(MidgardError::Status { path: path.to_string(), status: StatusCode::BAD_GATEWAY });
+            }
+            self.fixtures.get_json(path).await
+        }
+    }
+
+    #[actix_rt::test]
+    async fn discovers_available_pools() {
+        let client = FixtureMidgardClient::default();
+        assert_eq!(fetch_pools(&client).await.unwrap(), vec!["BTC.BTC", "ETH.ETH"]);
+    }
+
+    #[test]
+    fn numbers_placeholders_row_by_row() {
+        assert_eq!(values_placeholders(2, 3), "($1, $2, $3), ($4, $5, $6)");
+    }
+
+    #[actix_rt::test]
+    async fn incremental_fetch_resumes_after_latest_interval() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+
+        fetch_runepool_data(&pool, &client, Interval::Year).await.unwrap();
+        fetch_runepool_data(&pool, &client, Interval::Year).await.unwrap();
+
+        let requests = client.requests();
+        assert_eq!(requests.last().unwrap(), "/v2/history/runepool?interval=year&count=400&from=1700092800");
+    }
+
+    #[actix_rt::test]
+    async fn records_each_run_in_ingestion_runs() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+
+        fetch_runepool_data(&pool, &client, Interval::Month).await.unwrap();
+
+        let run = pool.get().await.unwrap().query_one(
+            "SELECT rows_inserted + rows_updated AS rows, http_attempts, finished_at IS NOT NULL AS finished, error
+             FROM ingestion_runs WHERE dataset = 'runepool' AND interval = 'month' ORDER BY id DESC LIMIT 1",
+            &[],
+        ).await.unwrap();
+        assert_eq!(run.get::<_, i64>("rows"), 2);
+        assert_eq!(run.get::<_, i32>("http_attempts"), 1);
+        assert!(run.get::<_, bool>("finished"));
+        assert_eq!(run.get::<_, Option<String>>("error"), None);
+    }
+
+    #[actix_rt::test]
+    async fn failed_pool_discovery_shows_in_ingestion_status() {
+        let pool = test_pool();
+        let client = FailingPool { pool: "/v2/pools", ..Default::default() };
+
+        assert!(fetch_swaps_data(&pool, &client, Interval::Quarter).await.is_err());
+
+        let statuses = Database::new(pool).find_ingestion_status().await.unwrap();
+        let status = statuses.iter().find(|status| status.dataset == "swaps" && status.interval == "quarter").unwrap();
+        assert!(status.last_error.as_deref().is_some_and(|error| error.contains("/v2/pools")), "{:?}", status.last_error);
+        assert_eq!(status.last_error_pool, None);
+    }
+
+    #[actix_rt::test]
+    async fn failing_pool_does_not_stop_the_others() {
+        let pool = test_pool();
+        let client = FailingPool { pool: "BTC.BTC", ..Default::default() };
+
+        let result = fetch_depth_data(&pool, &client, Interval::Hour).await;
+
+        assert!(result.unwrap_err().to_string().contains("BTC.BTC"));
+        assert!(client.fixtures.requests().iter().any(|r| r.starts_with("/v2/history/depths/ETH.ETH?interval=hour")));
+        let db_client = pool.get().await.unwrap();
+        let runs = db_client.query(
+            "SELECT DISTINCT ON (pool) pool, error FROM ingestion_runs WHERE dataset = 'depth' AND interval = 'hour' ORDER BY pool, id DESC",
+            &[],
+        ).await.unwrap();
+        let errors: Vec<(String, bool)> = runs.iter().map(|run| (run.get("pool"), run.get::<_, Option<String>>("error").is_some())).collect();
+        assert_eq!(errors, [("BTC.BTC".to_string(), true), ("ETH.ETH".to_string(), false)]);
+    }
+
+    async fn reset_runepool(pool: &Pool, interval: &str, starts: &[i64]) {
+        let db_client = pool.get().await.unwrap();
+        db_client.execute("DELETE FROM rune_pool_history WHERE interval = $1", &[&interval]).await.unwrap();
+        db_client.execute("DELETE FROM interval_gaps WHERE dataset = 'runepool' AND interval = $1", &[&interval]).await.unwrap();
+        for start in starts {
+            db_client.execute(
+                "INSERT INTO rune_pool_history (interval, start_time, end_time, count, units)
+                 VALUES ($1, to_timestamp($2::BIGINT), to_timestamp($2::BIGINT + 86400), 1, 1)",
+                &[&interval, start],
+            ).await.unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn repairs_gap_between_stored_intervals() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+        reset_runepool(&pool, "week", &[1699920000, 1700092800]).await;
+
+        repair_gaps(&pool, &client, Interval::Week).await.unwrap();
+
+        assert!(client.requests().contains(&"/v2/history/runepool?interval=week&count=400&from=1700006400".to_string()));
+        let db_client = pool.get().await.unwrap();
+        let filled: i64 = db_client.query_one(
+            "SELECT count FROM rune_pool_history WHERE interval = 'week' AND start_time = to_timestamp(1700006400)", &[],
+        ).await.unwrap().get(0);
+        assert_eq!(filled, 1487);
+        let open: i64 = db_client.query_one(
+            "SELECT COUNT(*) FROM interval_gaps WHERE dataset = 'runepool' AND interval = 'week' AND resolved_at IS NULL", &[],
+        ).await.unwrap().get(0);
+        assert_eq!(open, 0);
+    }
+
+    #[actix_rt::test]
+    async fn records_gap_midgard_cannot_fill() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+        reset_runepool(&pool, "5min", &[1700092800, 1700265600]).await;
+
+        repair_gaps(&pool, &client, Interval::FiveMin).await.unwrap();
+
+        let gap = pool.get().await.unwrap().query_one(
+            "SELECT EXTRACT(EPOCH FROM gap_start)::BIGINT AS gap_start, EXTRACT(EPOCH FROM gap_end)::BIGINT AS gap_end, repair_attempts
+             FROM interval_gaps WHERE dataset = 'runepool' AND interval = '5min' AND resolved_at IS NULL",
+            &[],
+        ).await.unwrap();
+        assert_eq!(gap.get::<_, i64>("gap_start"), 1700179200);
+        assert_eq!(gap.get::<_, i64>("gap_end"), 1700265600);
+        assert_eq!(gap.get::<_, i32>("repair_attempts"), 1);
+    }
+
+    #[actix_rt::test]
+    async fn failing_pool_gap_is_recorded_and_the_others_repaired() {
+        let pool = test_pool();
+        let client = FailingPool { pool: "BTC.BTC", ..Default::default() };
+        let db_client = pool.get().await.unwrap();
+        let clear = "DELETE FROM depth_price_history WHERE interval = 'month' AND pool IN ('BTC.BTC', 'ETH.ETH')";
+        db_client.execute(clear, &[]).await.unwrap();
+        db_client.execute("DELETE FROM interval_gaps WHERE dataset = 'depth' AND interval = 'month'", &[]).await.unwrap();
+        let depths: Vec<DepthPrice> = ["BTC.BTC", "ETH.ETH"].into_iter()
+            .flat_map(|asset| [1699920000, 1700092800].map(|start| {
+                let start = DateTime::from_timestamp(start, 0).unwrap();
+                test_depth(asset, "month", start, start + chrono::Duration::days(1))
+            }))
+            .collect();
+        insert_test_depths(&pool, &depths).await;
+
+        let result = repair_gaps(&pool, &client, Interval::Month).await;
+
+        assert!(result.unwrap_err().to_string().contains("depth BTC.BTC"));
+        assert!(client.fixtures.requests().contains(&"/v2/history/depths/ETH.ETH?interval=month&count=400&from=1700006400".to_string()));
+        let open: Vec<String> = db_client.query(
+            "SELECT pool FROM interval_gaps WHERE dataset = 'depth' AND interval = 'month' AND resolved_at IS NULL", &[],
+        ).await.unwrap().iter().map(|row| row.get(0)).collect();
+        assert_eq!(open, ["BTC.BTC"]);
+        db_client.execute(clear, &[]).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn refetched_earnings_drop_pools_no_longer_listed() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+        fetch_earnings_window(&pool, &client, Interval::Quarter, since(None)).await.unwrap();
+        let db_client = pool.get().await.unwrap();
+        db_client.execute(
+            "INSERT INTO pool_earnings (earnings_history_id, pool, asset_liquidity_fees, rune_liquidity_fees, total_liquidity_fees_rune, saver_earning, rewards, earnings)
+             SELECT id, 'GONE.TEST', 1, 1, 1, 1, 1, 1 FROM earnings_history WHERE interval = 'quarter' AND start_time = to_timestamp(1699920000)",
+            &[],
+        ).await.unwrap();
+
+        fetch_earnings_window(&pool, &client, Interval::Quarter, since(None)).await.unwrap();
+
+        let pools: Vec<String> = db_client.query(
+            "SELECT p.pool FROM pool_earnings p JOIN earnings_history e ON e.id = p.earnings_history_id
+             WHERE e.interval = 'quarter' AND e.start_time = to_timestamp(1699920000) ORDER BY p.pool",
+            &[],
+        ).await.unwrap().iter().map(|row| row.get(0)).collect();
+        assert_eq!(pools, ["BTC.BTC", "ETH.ETH"]);
+    }
+
+    #[actix_rt::test]
+    async fn partial_interval_is_refetched_and_overwritten() {
+        let pool = test_pool();
+        let client = FixtureMidgardClient::default();
+        pool.get().await.unwrap().execute(
+            "INSERT INTO rune_pool_history (interval, start_time, end_time, is_partial, count, units)
+             VALUES ('quarter', to_timestamp(1700006400), to_timestamp(1700092800), TRUE, 1, 1)
+             ON CONFLICT (interval, start_time, end_time) DO UPDATE SET is_partial = TRUE, count = 1, units = 1",
+            &[],
+        ).await.unwrap();
+
+        fetch_runepool_data(&pool, &client, Interval::Quarter).await.unwrap();
+
+        assert_eq!(client.requests(), vec!["/v2/history/runepool?interval=quarter&count=400&from=1700006400"]);
+        let row = pool.get().await.unwrap().query_one(
+            "SELECT count, is_partial FROM rune_pool_history WHERE interval = 'quarter' AND start_time = to_timestamp(1700006400)",
+            &[],
+        ).await.unwrap();
+        assert_eq!(row.get::<_, i64>("count"), 1487);
+        assert!(!row.get::<_, bool>("is_partial"));
+    }
+}
@@ -1,44 +1,159 @@
+use crate::midgard::MidgardClient;
+use crate::models::Interval;
 use crate::services::DepthService;
 use deadpool_postgres::Pool;
 use tokio_cron_scheduler::{JobScheduler, Job};
 use log::{info, error};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
 
-pub async fn setup_jobs(pool: Pool) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+pub async fn setup_jobs(pool: Pool, client: Arc<dyn MidgardClient>, intervals: Vec<Interval>) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let sched = JobScheduler::new().await?;
-    let client = reqwest::Client::new();
     let service = DepthService::new(pool.clone());
 
-    info!("Setting up scheduled job");
-    let job = Job::new_async("0 0 * * * *", move |_, _| { 
+    for &interval in &intervals {
+        info!("Setting up scheduled {} job", interval);
         let client = client.clone();
         let service = service.clone();
+        let job = Job::new_async(interval.schedule(), move |_, _| {
+            let client = client.clone();
+            let service = service.clone();
+            Box::pin(async move {
+                info!("Scheduled {} job running", interval);
+                run_ingestion(&service, client.as_ref(), interval).await;
+            })
+        })?;
+        sched.add(job).await?;
+    }
+
+    // Gap repair scans whole tables, so it runs hourly for every interval rather than per job.
+    let job = Job::new_async("0 30 * * * *", move |_, _| {
+        let client = client.clone();
+        let service = service.clone();
+        let intervals = intervals.clone();
         Box::pin(async move {
-            info!("Scheduled job running");
-            if let Err(e) = service.fetch_and_store_depths(&client).await {
-                error!("Depth error: {}", e);
-            } else {
-                info!("Depth data fetched and stored");
-            }
-            if let Err(e) = service.fetch_and_store_swaps(&client).await {
-                error!("Swaps error: {}", e);
-            } else {
-                info!("Swaps data fetched and stored");
-            }
-            if let Err(e) = service.fetch_and_store_earnings(&client).await {
-                error!("Earnings error: {}", e);
-            } else {
-                info!("Earnings data fetched and stored");
-            }
-            if let Err(e) = service.fetch_and_store_runepools(&client).await {
-                error!("Runepool error: {}", e);
-            } else {
-                info!("Runepool data fetched and stored");
-            }
+            info!("Scheduled gap repair running");
+            run_gap_repair(&service, client.as_ref(), &intervals).await;
         })
     })?;
-
     sched.add(job).await?;
+
     sched.start().await?;
     info!("Job scheduler started");
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn run_ingestion(service: &DepthService, client: &dyn MidgardClient, interval: Interval) {
+    if let Err(e) = service.fetch_and_store_depths(client, interval).await {
+        error!("Depth {} error: {}", interval, e);
+    } else {
+        info!("Depth {} data fetched and stored", interval);
+    }
+    if let Err(e) = service.fetch_and_store_swaps(client, interval).await {
+        error!("Swaps {} error: {}", interval, e);
+    } else {
+        info!("Swaps {} data fetched and stored", interval);
+    }
+    if let Err(e) = service.fetch_and_store_earnings(client, interval).await {
+        error!("Earnings {} error: {}", interval, e);
+    } else {
+        info!("Earnings {} data fetched and stored", interval);
+    }
+    if let Err(e) = service.fetch_and_store_runepools(client, interval).await {
+        error!("Runepool {} error: {}", interval, e);
+    } else {
+        info!("Runepool {} data fetched and stored", interval);
+    }
+}
+
+pub async fn run_gap_repair(service: &DepthService, client: &dyn MidgardClient, intervals: &[Interval]) {
+    for &interval in intervals {
+        if let Err(e) = service.repair_gaps(client, interval).await {
+            error!("Gap repair {} error: {}", interval, e);
+        } else {
+            info!("Gap repair {} finished", interval);
+        }
+    }
+}
+
+pub async fn run_backfill(pool: Pool, client: &dyn MidgardClient, intervals: Vec<Interval>, start: Option<DateTime<Utc>>) {
+    let service = DepthService::new(pool);
+
+    for interval in intervals {
+        info!("Backfill {} running", interval);
+        if let Err(e) = service.backfill_depths(client, interval, start).await {
+            error!("Depth {} backfill error: {}", interval, e);
+        } else {
+            info!("Depth {} history backfilled", interval);
+        }
+        if let Err(e) = service.backfill_swaps(client, interval, start).await {
+            error!("Swaps {} backfill error: {}", interval, e);
+        } else {
+            info!("Swaps {} history backfilled", interval);
+        }
+        if let Err(e) = service.backfill_earnings(client, interval, start).await {
+            error!("Earnings {} backfill error: {}", interval, e);
+        } else {
+            info!("Earnings {} history backfilled", interval);
+        }
+        if let Err(e) = service.backfill_runepools(client, interval, start).await {
+            error!("Runepool {} backfill error: {}", interval, e);
+        } else {
+            info!("Runepool {} history backfilled", interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::midgard::HttpMidgardClient;
+    use crate::midgard::mock_server::MockServer;
+    use crate::models::DepthPrice;
+
+    async fn count(pool: &Pool, query: &str) -> i64 {
+        pool.get().await.unwrap().query_one(query, &[]).await.unwrap().get(0)
+    }
+
+    #[actix_rt::test]
+    async fn ingests_recorded_midgard_data_over_http() {
+        let pool = test_pool();
+        let server = MockServer::start().await;
+        let client = HttpMidgardClient::new(&server.url);
+
+        run_ingestion(&DepthService::new(pool.clone()), &client, Interval::Day).await;
+
+        let requests = server.requests();
+        for expected in [
+            "/v2/pools?status=available",
+            "/v2/history/depths/BTC.BTC?interval=day",
+            "/v2/history/swaps?pool=BTC.BTC&interval=day",
+            "/v2/history/earnings?interval=day",
+            "/v2/history/runepool?interval=day",
+        ] {
+            assert!(requests.iter().any(|r| r.starts_with(expected)), "{} not in {:?}", expected, requests);
+        }
+
+        let window = "interval = 'day' AND start_time >= to_timestamp(1699920000) AND end_time <= to_timestamp(1700092800)";
+        for pool_name in ["BTC.BTC", "ETH.ETH"] {
+            for table in ["depth_price_history", "swaps_history"] {
+                let query = format!("SELECT COUNT(*) FROM {} WHERE pool = '{}' AND {}", table, pool_name, window);
+                assert_eq!(count(&pool, &query).await, 2, "{} {}", table, pool_name);
+            }
+        }
+        assert_eq!(count(&pool, &format!("SELECT COUNT(*) FROM earnings_history WHERE {}", window)).await, 2);
+        assert_eq!(count(&pool, &format!(
+            "SELECT COUNT(*) FROM pool_earnings p JOIN earnings_history e ON e.id = p.earnings_history_id WHERE {}", window,
+        )).await, 4);
+        assert_eq!(count(&pool, &format!("SELECT COUNT(*) FROM rune_pool_history WHERE {}", window)).await, 2);
+
+        // NUMERIC columns keep every digit Midgard sent.
+        let depth = DepthPrice::from(pool.get().await.unwrap().query_one(
+            "SELECT * FROM depth_price_history WHERE pool = 'BTC.BTC' AND interval = 'day' AND start_time = to_timestamp(1699920000)",
+            &[],
+        ).await.unwrap());
+        assert_eq!(depth.asset_price.to_string(), "9118.5034120410400647");
+        assert_eq!(depth.asset_price_usd.to_string(), "36602.8107130999997");
+    }
+}
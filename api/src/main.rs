@@ -6,12 +6,20 @@ use std::env;
 use url::Url;
 use log::info;
 use crate::routes::config;
+use crate::db::migrations::migrate;
 use crate::services::DepthService;
-use crate::jobs::setup_jobs;
+use crate::jobs::{setup_jobs, run_backfill};
+use crate::midgard::{HttpMidgardClient, DEFAULT_BASE_URL};
+use crate::midgard::retry::RetryPolicy;
+use crate::models::Interval;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
 
 mod db;
 mod fetcher;
 mod jobs;
+mod midgard;
 mod models;
 mod routes;
 mod services;
@@ -35,8 +43,32 @@ async fn main() -> std::io::Result<()> {
     let pool = pg_config.create_pool(Some(Runtime::Tokio1), tokio_postgres::NoTls)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
+    if env::args().nth(1).as_deref() == Some("migrate") {
+        let dry_run = env::args().nth(2).as_deref() == Some("--dry-run");
+        let versions = migrate(&pool, dry_run).await.map_err(|e| std::io::Error::other(e.to_string()))?;
+        info!("{} {} migration(s): {:?}", if dry_run { "Pending" } else { "Applied" }, versions.len(), versions);
+        return Ok(());
+    }
+    if env::var("AUTO_MIGRATE").map(|v| v != "false").unwrap_or(true) {
+        migrate(&pool, false).await.map_err(|e| std::io::Error::other(e.to_string()))?;
+    }
+
+    let midgard_url = env::var("MIDGARD_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+    let client = HttpMidgardClient::new(&midgard_url).with_retry_policy(retry_policy_from_env()?);
+
+    if env::args().nth(1).as_deref() == Some("backfill") {
+        let start = env::var("BACKFILL_START").ok()
+            .map(|s| DateTime::parse_from_rfc3339(&s).map(|d| d.with_timezone(&Utc)))
+            .transpose()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid BACKFILL_START: {}", e)))?;
+        let intervals = intervals_from_env("BACKFILL_INTERVALS", &[Interval::Day])?;
+        run_backfill(pool, &client, intervals, start).await;
+        return Ok(());
+    }
+
     let service = DepthService::new(pool.clone());
-    setup_jobs(pool.clone()).await.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let intervals = intervals_from_env("INGEST_INTERVALS", &Interval::ALL)?;
+    setup_jobs(pool.clone(), Arc::new(client), intervals).await.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let bind_address = format!("0.0.0.0:{}", port);
@@ -49,6 +81,7 @@ async fn main() -> std::io::Result<()> {
                     .allowed_origin("https://editor.swagger.io")
                     .allowed_methods(vec!["GET"])
                     .allowed_headers(vec![actix_web::http::header::ACCEPT])
+                    .expose_headers(vec!["X-Next-Cursor", "X-Prev-Cursor", "X-Request-Id"])
                     .supports_credentials()
             )
             .app_data(web::Data::new(service.clone()))
@@ -60,6 +93,40 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+// Reads a comma-separated interval list such as "hour,day" from `var`, falling back to `default`.
+fn intervals_from_env(var: &str, default: &[Interval]) -> std::io::Result<Vec<Interval>> {
+    match env::var(var) {
+        Ok(value) => value
+            .split(',')
+            .map(|s| s.trim().parse::<Interval>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid {}: {}", var, e))),
+        Err(_) => Ok(default.to_vec()),
+    }
+}
+
+// Overrides the default Midgard retry policy with MIDGARD_MAX_ATTEMPTS, MIDGARD_TIMEOUT_SECS and
+// MIDGARD_DEADLINE_SECS when set.
+fn retry_policy_from_env() -> std::io::Result<RetryPolicy> {
+    let number = |var: &str| -> std::io::Result<Option<u64>> {
+        env::var(var).ok()
+            .map(|v| v.trim().parse::<u64>())
+            .transpose()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid {}: {}", var, e)))
+    };
+    let mut policy = RetryPolicy::default();
+    if let Some(attempts) = number("MIDGARD_MAX_ATTEMPTS")? {
+        policy.max_attempts = attempts.max(1) as u32;
+    }
+    if let Some(secs) = number("MIDGARD_TIMEOUT_SECS")? {
+        policy.request_timeout = Duration::from_secs(secs);
+    }
+    if let Some(secs) = number("MIDGARD_DEADLINE_SECS")? {
+        policy.deadline = Duration::from_secs(secs);
+    }
+    Ok(policy)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +156,21 @@ mod tests {
             assert!(resp.status().is_success(), "Failed for {}", endpoint);
         }
     }
+
+    #[actix_rt::test]
+    async fn unreachable_database_is_503() {
+        let mut pg_config = PgConfig::new();
+        pg_config.host = Some("127.0.0.1".to_string());
+        pg_config.port = Some(1);
+        pg_config.dbname = Some("api".to_string());
+        let pool = pg_config.create_pool(Some(Runtime::Tokio1), tokio_postgres::NoTls).unwrap();
+        let app = test::init_service(App::new().app_data(web::Data::new(DepthService::new(pool))).configure(config)).await;
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/api/depth-history").to_request()).await;
+
+        assert_eq!(resp.status().as_u16(), 503);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "service_unavailable");
+        assert!(body["request_id"].as_str().is_some_and(|id| !id.is_empty()));
+    }
 }
\ No newline at end of file
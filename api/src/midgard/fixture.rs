@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use super::{record_attempt, MidgardClient, MidgardError};
+
+// Recorded Midgard responses, one file per endpoint and pool. Query parameters other than the
+// swaps `pool` are ignored, so every interval and range gets the same recording.
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/midgard");
+
+fn fixture_name(path: &str) -> Option<String> {
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    let param = |key: &str| {
+        query.split('&').find_map(|pair| pair.strip_prefix(key)?.strip_prefix('=').map(String::from))
+    };
+    match route {
+        "/v2/pools" => Some("pools".to_string()),
+        "/v2/history/earnings" => Some("earnings".to_string()),
+        "/v2/history/runepool" => Some("runepool".to_string()),
+        "/v2/history/swaps" => param("pool").map(|pool| format!("swaps_{}", pool)),
+        _ => route.strip_prefix("/v2/history/depths/").map(|asset| format!("depths_{}", asset)),
+    }
+}
+
+pub fn load(path: &str) -> Option<serde_json::Value> {
+    let name = fixture_name(path)?;
+    let body = std::fs::read_to_string(format!("{}/{}.json", FIXTURE_DIR, name)).ok()?;
+    Some(serde_json::from_str(&body).unwrap_or_else(|e| panic!("Invalid fixture {}: {}", name, e)))
+}
+
+#[derive(Default)]
+pub struct FixtureMidgardClient {
+    requests: Mutex<Vec<String>>,
+}
+
+impl FixtureMidgardClient {
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl MidgardClient for FixtureMidgardClient {
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, MidgardError> {
+        self.requests.lock().unwrap().push(path.to_string());
+        record_attempt();
+        load(path).ok_or_else(|| MidgardError::Status { path: path.to_string(), status: StatusCode::NOT_FOUND })
+    }
+}
@@ -0,0 +1,75 @@
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web::dev::ServerHandle;
+use actix_web::http::StatusCode;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use super::fixture;
+
+// A canned error response served instead of the fixture, with an optional Retry-After value.
+type Failure = (StatusCode, Option<&'static str>);
+
+// A local HTTP server replaying the recorded fixtures, so `HttpMidgardClient` can be exercised
+// end-to-end without the network.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    failures: Arc<Mutex<VecDeque<Failure>>>,
+    handle: ServerHandle,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let failures = Arc::new(Mutex::new(VecDeque::new()));
+        let recorded = requests.clone();
+        let scripted = failures.clone();
+        let server = HttpServer::new(move || {
+            let recorded = recorded.clone();
+            let scripted = scripted.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let recorded = recorded.clone();
+                let scripted = scripted.clone();
+                async move {
+                    let path = req.uri().to_string();
+                    recorded.lock().unwrap().push(path.clone());
+                    if let Some((status, retry_after)) = scripted.lock().unwrap().pop_front() {
+                        let mut response = HttpResponse::build(status);
+                        if let Some(retry_after) = retry_after {
+                            response.insert_header(("Retry-After", retry_after));
+                        }
+                        return response.finish();
+                    }
+                    match fixture::load(&path) {
+                        Some(json) => HttpResponse::Ok().json(json),
+                        None => HttpResponse::NotFound().finish(),
+                    }
+                }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("bind mock Midgard server");
+        let url = format!("http://{}", server.addrs()[0]);
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+        Self { url, requests, failures, handle }
+    }
+
+    // Answers the next request with `status` instead of the fixture.
+    pub fn fail_next(&self, status: u16, retry_after: Option<&'static str>) {
+        let status = StatusCode::from_u16(status).expect("valid status code");
+        self.failures.lock().unwrap().push_back((status, retry_after));
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // The stop command is sent immediately; the returned future only awaits completion.
+        drop(self.handle.stop(false));
+    }
+}
@@ -0,0 +1,207 @@
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use std::cell::Cell;
+use std::future::Future;
+use std::time::Instant;
+use thiserror::Error;
+use tokio::time::sleep;
+use crate::models::Interval;
+use retry::{retry_after, RetryPolicy};
+use crate::models::midgard::{decode_history, DecodeError, DepthHistory, SwapHistory, EarningsHistory, RunePoolHistory, PoolSummary};
+
+pub mod retry;
+#[cfg(test)]
+pub mod fixture;
+#[cfg(test)]
+pub mod mock_server;
+
+pub const DEFAULT_BASE_URL: &str = "https://midgard.ninerealms.com";
+// Midgard caps every history request at 400 intervals.
+pub const MAX_INTERVALS: usize = 400;
+
+tokio::task_local! {
+    static ATTEMPTS: Cell<u32>;
+}
+
+// Runs `f` and counts the HTTP requests clients send on its behalf, retries included.
+pub async fn count_attempts<F: Future>(f: F) -> (F::Output, u32) {
+    ATTEMPTS.scope(Cell::new(0), async {
+        let output = f.await;
+        (output, ATTEMPTS.with(Cell::get))
+    }).await
+}
+
+// Called by clients for every request sent; a no-op outside `count_attempts`.
+pub fn record_attempt() {
+    let _ = ATTEMPTS.try_with(|attempts| attempts.set(attempts.get() + 1));
+}
+
+#[derive(Debug, Error)]
+pub enum MidgardError {
+    #[error("request to {path} failed: {source}")]
+    Request { path: String, #[source] source: reqwest::Error },
+    #[error("{path} returned HTTP {status}")]
+    Status { path: String, status: StatusCode },
+    #[error("{path} failed after {attempts} attempts: {last}")]
+    Exhausted { path: String, attempts: u32, last: Box<MidgardError> },
+    #[error("malformed Midgard response from {path}: {source}")]
+    Json { path: String, #[source] source: serde_json::Error },
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+// Everything the fetchers need from Midgard. Implementations only provide `get_json`; the typed
+// endpoint methods build paths and decode on top of it. `range` is an extra query-string suffix
+// such as `&from=1700000000`.
+#[async_trait]
+pub trait MidgardClient: Send + Sync {
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, MidgardError>;
+
+    async fn pools(&self) -> Result<Vec<PoolSummary>, MidgardError> {
+        let path = "/v2/pools?status=available";
+        let json = self.get_json(path).await?;
+        serde_json::from_value(json).map_err(|source| MidgardError::Json { path: path.to_string(), source })
+    }
+
+    async fn depth_history(&self, asset: &str, interval: Interval, range: &str) -> Result<DepthHistory, MidgardError> {
+        let path = format!("/v2/history/depths/{}?interval={}&count={}{}", asset, interval, MAX_INTERVALS, range);
+        Ok(decode_history(self.get_json(&path).await?)?)
+    }
+
+    async fn swap_history(&self, asset: &str, interval: Interval, range: &str) -> Result<SwapHistory, MidgardError> {
+        let path = format!("/v2/history/swaps?pool={}&interval={}&count={}{}", asset, interval, MAX_INTERVALS, range);
+        Ok(decode_history(self.get_json(&path).await?)?)
+    }
+
+    async fn earnings_history(&self, interval: Interval, range: &str) -> Result<EarningsHistory, MidgardError> {
+        let path = format!("/v2/history/earnings?interval={}&count={}{}", interval, MAX_INTERVALS, range);
+        Ok(decode_history(self.get_json(&path).await?)?)
+    }
+
+    async fn runepool_history(&self, interval: Interval, range: &str) -> Result<RunePoolHistory, MidgardError> {
+        let path = format!("/v2/history/runepool?interval={}&count={}{}", interval, MAX_INTERVALS, range);
+        Ok(decode_history(self.get_json(&path).await?)?)
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpMidgardClient {
+    client: Client,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl HttpMidgardClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[async_trait]
+impl MidgardClient for HttpMidgardClient {
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, MidgardError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request_error = |source| MidgardError::Request { path: path.to_string(), source };
+        let started = Instant::now();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            record_attempt();
+            let (error, wait) = match self.client.get(&url).timeout(self.retry.attempt_timeout(started.elapsed())).send().await {
+                Ok(resp) if resp.status().is_success() => match resp.json().await {
+                    Ok(json) => return Ok(json),
+                    Err(e) if self.retry.is_retryable_error(&e) => (request_error(e), None),
+                    Err(e) => return Err(request_error(e)),
+                },
+                Ok(resp) if self.retry.is_retryable_status(resp.status()) => {
+                    let wait = retry_after(resp.headers());
+                    (MidgardError::Status { path: path.to_string(), status: resp.status() }, wait)
+                }
+                Ok(resp) => return Err(MidgardError::Status { path: path.to_string(), status: resp.status() }),
+                Err(e) if self.retry.is_retryable_error(&e) => (request_error(e), None),
+                Err(e) => return Err(request_error(e)),
+            };
+            let delay = self.retry.delay(attempts, wait);
+            if attempts >= self.retry.max_attempts || started.elapsed() + delay > self.retry.deadline {
+                return Err(MidgardError::Exhausted { path: path.to_string(), attempts, last: Box::new(error) });
+            }
+            sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::mock_server::MockServer;
+    use std::time::Duration;
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn retries_transient_statuses_and_honors_retry_after() {
+        let server = MockServer::start().await;
+        server.fail_next(503, None);
+        server.fail_next(429, Some("0"));
+        let client = HttpMidgardClient::new(&server.url).with_retry_policy(fast_retries());
+
+        let pools = client.pools().await.unwrap();
+
+        assert_eq!(pools.len(), 2);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn gives_up_after_max_attempts() {
+        let server = MockServer::start().await;
+        for _ in 0..3 {
+            server.fail_next(502, None);
+        }
+        let client = HttpMidgardClient::new(&server.url).with_retry_policy(fast_retries());
+
+        let err = client.pools().await.unwrap_err();
+
+        assert!(matches!(err, MidgardError::Exhausted { attempts: 3, .. }), "{}", err);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn does_not_retry_client_errors() {
+        let server = MockServer::start().await;
+        server.fail_next(400, None);
+        let client = HttpMidgardClient::new(&server.url).with_retry_policy(fast_retries());
+
+        let err = client.pools().await.unwrap_err();
+
+        assert!(matches!(err, MidgardError::Status { status: StatusCode::BAD_REQUEST, .. }), "{}", err);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn retry_after_beyond_deadline_stops_early() {
+        let server = MockServer::start().await;
+        server.fail_next(429, Some("120"));
+        let policy = RetryPolicy { deadline: Duration::from_secs(5), ..fast_retries() };
+        let client = HttpMidgardClient::new(&server.url).with_retry_policy(policy);
+
+        let err = client.pools().await.unwrap_err();
+
+        assert!(matches!(err, MidgardError::Exhausted { attempts: 1, .. }), "{}", err);
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retryable_statuses: Vec<StatusCode>,
+    // Applies to each attempt, including reading the body, and is cut short by `deadline`.
+    pub request_timeout: Duration,
+    // Upper bound on all attempts and waits together.
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            request_timeout: Duration::from_secs(30),
+            deadline: Duration::from_secs(180),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_retryable_status(&self, status: StatusCode) -> bool {
+        self.retryable_statuses.contains(&status)
+    }
+
+    // Timeouts and connection failures (refused, reset) are worth another attempt; anything
+    // else, such as an undecodable body, would fail the same way again.
+    pub fn is_retryable_error(&self, error: &reqwest::Error) -> bool {
+        error.is_timeout() || error.is_connect() || error.is_request()
+    }
+
+    // The timeout for an attempt made `elapsed` after the first, so it cannot run past `deadline`.
+    pub fn attempt_timeout(&self, elapsed: Duration) -> Duration {
+        self.request_timeout.min(self.deadline.saturating_sub(elapsed))
+    }
+
+    // Full-jitter exponential backoff: a random wait up to `base_delay * 2^(attempt - 1)`,
+    // capped at `max_delay`. A server-sent Retry-After takes precedence.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let ceiling = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        ceiling.mul_f64(fastrand::f64())
+    }
+}
+
+// Retry-After is either a number of seconds or an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy { max_delay: Duration::from_secs(5), ..RetryPolicy::default() };
+        for _ in 0..100 {
+            assert!(policy.delay(1, None) <= Duration::from_secs(1));
+            assert!(policy.delay(3, None) <= Duration::from_secs(4));
+            assert!(policy.delay(10, None) <= Duration::from_secs(5));
+        }
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(42))), Duration::from_secs(42));
+    }
+
+    #[test]
+    fn attempts_end_by_the_deadline() {
+        let policy = RetryPolicy { request_timeout: Duration::from_secs(30), deadline: Duration::from_secs(60), ..RetryPolicy::default() };
+        assert_eq!(policy.attempt_timeout(Duration::from_secs(10)), Duration::from_secs(30));
+        assert_eq!(policy.attempt_timeout(Duration::from_secs(50)), Duration::from_secs(10));
+        assert_eq!(policy.attempt_timeout(Duration::from_secs(70)), Duration::ZERO);
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use tokio_postgres::Row;
+use super::{deserialize_list, Interval};
+
+// How `/api/{dataset}/aggregate` rolls up stored intervals: `fields` of the rows in each `bucket`
+// are combined with `agg`. The usual history parameters pick the rows.
+#[derive(Debug, Deserialize)]
+pub struct AggregateParams {
+    #[serde(default = "default_bucket")]
+    pub bucket: Interval,
+    #[serde(default)]
+    pub agg: AggregateFn,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub fields: Option<Vec<String>>,
+}
+
+fn default_bucket() -> Interval {
+    Interval::Week
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AggregateFn {
+    #[default]
+    Sum,
+    Avg,
+    Min,
+    Max,
+    First,
+    Last,
+}
+
+impl AggregateFn {
+    // The aggregate over `column`, as NUMERIC so sums of BIGINT columns cannot overflow. First and
+    // last go by start_time.
+    pub fn as_sql(self, column: &str) -> String {
+        match self {
+            AggregateFn::Sum => format!("SUM({})::NUMERIC", column),
+            AggregateFn::Avg => format!("AVG({})::NUMERIC", column),
+            AggregateFn::Min => format!("MIN({})::NUMERIC", column),
+            AggregateFn::Max => format!("MAX({})::NUMERIC", column),
+            AggregateFn::First => format!("((ARRAY_AGG({} ORDER BY start_time ASC))[1])::NUMERIC", column),
+            AggregateFn::Last => format!("((ARRAY_AGG({} ORDER BY start_time DESC))[1])::NUMERIC", column),
+        }
+    }
+}
+
+// One bucket of a rollup. `pool` is only set for datasets that are split by pool, and
+// `is_partial` when any interval in the bucket was still open.
+#[derive(Debug, Serialize)]
+pub struct Aggregate {
+    pub bucket_start: DateTime<Utc>,
+    pub bucket_end: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    pub row_count: i64,
+    pub is_partial: bool,
+    pub values: BTreeMap<String, Option<Decimal>>,
+}
+
+const FIXED_COLUMNS: &[&str] = &["bucket_start", "bucket_end", "pool", "row_count", "is_partial"];
+
+impl From<Row> for Aggregate {
+    fn from(row: Row) -> Self {
+        let values = row.columns().iter()
+            .map(|column| column.name())
+            .filter(|name| !FIXED_COLUMNS.contains(name))
+            .map(|name| (name.to_string(), row.get(name)))
+            .collect();
+        Self {
+            bucket_start: row.get("bucket_start"),
+            bucket_end: row.get("bucket_end"),
+            pool: row.try_get("pool").ok(),
+            row_count: row.get("row_count"),
+            is_partial: row.get("is_partial"),
+            values,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use tokio_postgres::Row;
+use super::Interval;
+
+#[derive(Debug, Deserialize)]
+pub struct CandleParams {
+    #[serde(default)]
+    pub bucket: Interval,
+    #[serde(default)]
+    pub quote: Quote,
+}
+
+// Which price the candles follow: asset_price_usd, or asset_price (the asset's price in RUNE).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quote {
+    #[default]
+    Usd,
+    Rune,
+}
+
+impl Quote {
+    pub fn column(self) -> &'static str {
+        match self {
+            Quote::Usd => "asset_price_usd",
+            Quote::Rune => "asset_price",
+        }
+    }
+}
+
+// An OHLC bar over the depth history of one pool. Depth history holds one price per interval, so
+// the bar is built from the prices of the `source_interval` rows inside the bucket. Volume is
+// swaps_history.total_volume (in RUNE base units) inside the bucket, null when none is stored.
+#[derive(Debug, Serialize)]
+pub struct Candle {
+    pub bucket_start: DateTime<Utc>,
+    pub bucket_end: DateTime<Utc>,
+    pub source_interval: String,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Option<Decimal>,
+    pub volume_usd: Option<Decimal>,
+    pub row_count: i64,
+    pub is_partial: bool,
+}
+
+impl From<Row> for Candle {
+    fn from(row: Row) -> Self {
+        Self {
+            bucket_start: row.get("bucket_start"),
+            bucket_end: row.get("bucket_end"),
+            source_interval: row.get("source_interval"),
+            open: row.get("open"),
+            high: row.get("high"),
+            low: row.get("low"),
+            close: row.get("close"),
+            volume: row.get("volume"),
+            volume_usd: row.get("volume_usd"),
+            row_count: row.get("row_count"),
+            is_partial: row.get("is_partial"),
+        }
+    }
+}
@@ -1,23 +1,27 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use tokio_postgres::Row;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DepthPrice {
     pub id: i32,
     pub pool: String,
+    pub interval: String,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    // The interval had not closed yet when it was last fetched.
+    pub is_partial: bool,
     pub asset_depth: i64,
     pub rune_depth: i64,
-    pub asset_price: f64,
-    pub asset_price_usd: f64,
+    pub asset_price: Decimal,
+    pub asset_price_usd: Decimal,
     pub liquidity_units: i64,
     pub members_count: i64,
     pub synth_units: i64,
     pub synth_supply: i64,
     pub units: i64,
-    pub luvi: f64,
+    pub luvi: Decimal,
 }
 
 impl From<Row> for DepthPrice {
@@ -25,8 +29,10 @@ impl From<Row> for DepthPrice {
         Self {
             id: row.get("id"),
             pool: row.get("pool"),
+            interval: row.get("interval"),
             start_time: row.get("start_time"),
             end_time: row.get("end_time"),
+            is_partial: row.get("is_partial"),
             asset_depth: row.get("asset_depth"),
             rune_depth: row.get("rune_depth"),
             asset_price: row.get("asset_price"),
@@ -1,27 +1,38 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use tokio_postgres::Row;
+use tokio_postgres::types::Json;
+use super::PoolEarning;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Earnings {
     pub id: i32,
+    pub interval: String,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    // The interval had not closed yet when it was last fetched.
+    pub is_partial: bool,
     pub liquidity_fees: i64,
     pub block_rewards: i64,
     pub earnings: i64,
     pub bonding_earnings: i64,
     pub liquidity_earnings: i64,
-    pub avg_node_count: f64,
-    pub rune_price_usd: f64,
+    pub avg_node_count: Decimal,
+    pub rune_price_usd: Decimal,
+    // The per-pool split, only read with `include=pools`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pools: Option<Vec<PoolEarning>>,
 }
 
 impl From<Row> for Earnings {
     fn from(row: Row) -> Self {
         Self {
             id: row.get("id"),
+            interval: row.get("interval"),
             start_time: row.get("start_time"),
             end_time: row.get("end_time"),
+            is_partial: row.get("is_partial"),
             liquidity_fees: row.get("liquidity_fees"),
             block_rewards: row.get("block_rewards"),
             earnings: row.get("earnings"),
@@ -29,6 +40,8 @@ impl From<Row> for Earnings {
             liquidity_earnings: row.get("liquidity_earnings"),
             avg_node_count: row.get("avg_node_count"),
             rune_price_usd: row.get("rune_price_usd"),
+            pools: row.columns().iter().any(|column| column.name() == "pools")
+                .then(|| row.get::<_, Json<Vec<PoolEarning>>>("pools").0),
         }
     }
 }
\ No newline at end of file
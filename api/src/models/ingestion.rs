@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use tokio_postgres::Row;
+
+// Freshness of one dataset at one granularity, summarised from ingestion_runs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IngestionStatus {
+    pub dataset: String,
+    pub interval: String,
+    pub last_run_at: DateTime<Utc>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_pool: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub rows_inserted_24h: i64,
+    pub rows_updated_24h: i64,
+    pub http_attempts_24h: i64,
+}
+
+impl From<Row> for IngestionStatus {
+    fn from(row: Row) -> Self {
+        Self {
+            dataset: row.get("dataset"),
+            interval: row.get("interval"),
+            last_run_at: row.get("last_run_at"),
+            last_success_at: row.get("last_success_at"),
+            last_error: row.get("last_error"),
+            last_error_pool: row.get("last_error_pool"),
+            last_error_at: row.get("last_error_at"),
+            rows_inserted_24h: row.get("rows_inserted_24h"),
+            rows_updated_24h: row.get("rows_updated_24h"),
+            http_attempts_24h: row.get("http_attempts_24h"),
+        }
+    }
+}
+
+// A range of missing intervals that gap repair could not fill from Midgard.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntervalGap {
+    pub id: i32,
+    pub dataset: String,
+    pub pool: Option<String>,
+    pub interval: String,
+    pub gap_start: DateTime<Utc>,
+    pub gap_end: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+    pub last_attempt_at: DateTime<Utc>,
+    pub repair_attempts: i32,
+}
+
+impl From<Row> for IntervalGap {
+    fn from(row: Row) -> Self {
+        Self {
+            id: row.get("id"),
+            dataset: row.get("dataset"),
+            pool: row.get("pool"),
+            interval: row.get("interval"),
+            gap_start: row.get("gap_start"),
+            gap_end: row.get("gap_end"),
+            detected_at: row.get("detected_at"),
+            last_attempt_at: row.get("last_attempt_at"),
+            repair_attempts: row.get("repair_attempts"),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[serde(rename = "5min")]
+    FiveMin,
+    Hour,
+    #[default]
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Interval {
+    pub const ALL: [Interval; 7] = [
+        Interval::FiveMin,
+        Interval::Hour,
+        Interval::Day,
+        Interval::Week,
+        Interval::Month,
+        Interval::Quarter,
+        Interval::Year,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::FiveMin => "5min",
+            Interval::Hour => "hour",
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+            Interval::Quarter => "quarter",
+            Interval::Year => "year",
+        }
+    }
+
+    // The length of one interval as a Postgres INTERVAL literal.
+    pub fn as_pg_interval(&self) -> &'static str {
+        match self {
+            Interval::FiveMin => "5 minutes",
+            Interval::Hour => "1 hour",
+            Interval::Day => "1 day",
+            Interval::Week => "1 week",
+            Interval::Month => "1 month",
+            Interval::Quarter => "3 months",
+            Interval::Year => "1 year",
+        }
+    }
+
+    // Cron schedule for refreshing this granularity, roughly as often as a new interval can close.
+    pub fn schedule(&self) -> &'static str {
+        match self {
+            Interval::FiveMin => "0 */5 * * * *",
+            Interval::Hour => "0 1 * * * *",
+            Interval::Day => "0 0 * * * *",
+            Interval::Week => "0 10 */6 * * *",
+            Interval::Month | Interval::Quarter | Interval::Year => "0 20 0 * * *",
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Interval::ALL
+            .into_iter()
+            .find(|interval| interval.as_str() == s)
+            .ok_or_else(|| format!("Unknown interval '{}'", s))
+    }
+}
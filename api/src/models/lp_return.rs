@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use tokio_postgres::Row;
+use super::Interval;
+
+#[derive(Debug, Deserialize)]
+pub struct LpReturnParams {
+    pub entry: DateTime<Utc>,
+    // Now when left out.
+    pub exit: Option<DateTime<Utc>>,
+    // What the position was worth when it was opened, half in each asset.
+    #[serde(default = "default_deposit")]
+    pub deposit_usd: Decimal,
+    // The depth series to read; the finest one with a closed interval by `entry` when left out.
+    pub interval: Option<Interval>,
+}
+
+fn default_deposit() -> Decimal {
+    Decimal::from(1000)
+}
+
+// A symmetric LP position from `entry_time` to `exit_time`, both the end of the latest closed
+// interval of the `interval` depth series by then, against holding the deposited assets. The pool's
+// price moves by `price_ratio` (exit over entry asset_price), which alone costs `impermanent_loss`;
+// fees and rewards grow each liquidity unit by `luvi_growth`, with LUVI = sqrt(asset_depth *
+// rune_depth) / units. `lp_vs_hold` combines both. Amounts are in whole assets, rates are fractions.
+#[derive(Debug, Serialize)]
+pub struct LpReturn {
+    pub pool: String,
+    pub interval: String,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    pub entry_asset_price: Decimal,
+    pub exit_asset_price: Decimal,
+    pub entry_asset_price_usd: Decimal,
+    pub exit_asset_price_usd: Decimal,
+    pub entry_rune_price_usd: Decimal,
+    pub exit_rune_price_usd: Decimal,
+    pub price_ratio: Decimal,
+    pub entry_luvi: Decimal,
+    pub exit_luvi: Decimal,
+    pub luvi_growth: Decimal,
+    pub impermanent_loss: Decimal,
+    pub lp_vs_hold: Decimal,
+    pub deposit_usd: Decimal,
+    pub entry_asset_amount: Decimal,
+    pub entry_rune_amount: Decimal,
+    pub exit_asset_amount: Decimal,
+    pub exit_rune_amount: Decimal,
+    pub lp_value_usd: Decimal,
+    pub hold_value_usd: Decimal,
+    pub lp_return: Decimal,
+    pub hold_return: Decimal,
+}
+
+impl From<Row> for LpReturn {
+    fn from(row: Row) -> Self {
+        Self {
+            pool: row.get("pool"),
+            interval: row.get("interval"),
+            entry_time: row.get("entry_time"),
+            exit_time: row.get("exit_time"),
+            entry_asset_price: row.get("entry_asset_price"),
+            exit_asset_price: row.get("exit_asset_price"),
+            entry_asset_price_usd: row.get("entry_asset_price_usd"),
+            exit_asset_price_usd: row.get("exit_asset_price_usd"),
+            entry_rune_price_usd: row.get("entry_rune_price_usd"),
+            exit_rune_price_usd: row.get("exit_rune_price_usd"),
+            price_ratio: row.get("price_ratio"),
+            entry_luvi: row.get("entry_luvi"),
+            exit_luvi: row.get("exit_luvi"),
+            luvi_growth: row.get("luvi_growth"),
+            impermanent_loss: row.get("impermanent_loss"),
+            lp_vs_hold: row.get("lp_vs_hold"),
+            deposit_usd: row.get("deposit_usd"),
+            entry_asset_amount: row.get("entry_asset_amount"),
+            entry_rune_amount: row.get("entry_rune_amount"),
+            exit_asset_amount: row.get("exit_asset_amount"),
+            exit_rune_amount: row.get("exit_rune_amount"),
+            lp_value_usd: row.get("lp_value_usd"),
+            hold_value_usd: row.get("hold_value_usd"),
+            lp_return: row.get("lp_return"),
+            hold_return: row.get("hold_return"),
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use thiserror::Error;
+use super::{DepthPrice, Swap, Earnings, PoolEarning, RunePool};
+
+// Midgard encodes every number as a string, so the response types keep them as strings and the
+// `TryFrom` conversions into the stored models do the parsing, naming the field that failed.
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("malformed Midgard response: {0}")]
+    Response(#[source] serde_json::Error),
+    #[error("malformed Midgard meta: {0}")]
+    Meta(#[source] serde_json::Error),
+    #[error("malformed Midgard interval {index} (startTime {start_time}): {source}")]
+    Interval { index: usize, start_time: String, #[source] source: serde_json::Error },
+    #[error("invalid value {value:?} for field `{field}` in Midgard interval starting {start_time}")]
+    Field { field: &'static str, start_time: String, value: String },
+}
+
+#[derive(Debug)]
+pub struct History<M, I> {
+    pub meta: M,
+    pub intervals: Vec<I>,
+}
+
+pub type DepthHistory = History<DepthMeta, DepthInterval>;
+pub type SwapHistory = History<SwapInterval, SwapInterval>;
+pub type EarningsHistory = History<EarningsInterval, EarningsInterval>;
+pub type RunePoolHistory = History<RunePoolMeta, RunePoolInterval>;
+
+// Decodes a history response one interval at a time so a bad entry is reported with its position.
+pub fn decode_history<M: DeserializeOwned, I: DeserializeOwned>(json: serde_json::Value) -> Result<History<M, I>, DecodeError> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct RawHistory {
+        meta: serde_json::Value,
+        intervals: Vec<serde_json::Value>,
+    }
+
+    let raw: RawHistory = serde_json::from_value(json).map_err(DecodeError::Response)?;
+    let meta = serde_json::from_value(raw.meta).map_err(DecodeError::Meta)?;
+    let intervals = raw.intervals
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let start_time = value["startTime"].as_str().unwrap_or("unknown").to_string();
+            serde_json::from_value(value).map_err(|source| DecodeError::Interval { index, start_time, source })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(History { meta, intervals })
+}
+
+fn parse_field<T: FromStr>(field: &'static str, start_time: &str, value: &str) -> Result<T, DecodeError> {
+    value.parse().map_err(|_| DecodeError::Field {
+        field,
+        start_time: start_time.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_time(field: &'static str, start_time: &str, value: &str) -> Result<DateTime<Utc>, DecodeError> {
+    let seconds: i64 = parse_field(field, start_time, value)?;
+    DateTime::from_timestamp(seconds, 0).ok_or_else(|| DecodeError::Field {
+        field,
+        start_time: start_time.to_string(),
+        value: value.to_string(),
+    })
+}
+
+// Midgard's newest interval ends in the future and keeps changing until it closes.
+// A malformed endTime is reported by `parse_time`.
+fn is_open(end_time: &str) -> bool {
+    end_time.parse::<i64>().is_ok_and(|seconds| seconds > Utc::now().timestamp())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PoolSummary {
+    pub asset: String,
+}
+
+// Meta blocks are decoded so a schema change fails loudly, even where no field is read yet.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DepthMeta {
+    pub start_time: String,
+    pub end_time: String,
+    pub price_shift_loss: String,
+    pub luvi_increase: String,
+    pub start_asset_depth: String,
+    pub start_rune_depth: String,
+    #[serde(rename = "startLPUnits")]
+    pub start_lp_units: String,
+    pub start_member_count: String,
+    pub start_synth_units: String,
+    pub end_asset_depth: String,
+    pub end_rune_depth: String,
+    #[serde(rename = "endLPUnits")]
+    pub end_lp_units: String,
+    pub end_member_count: String,
+    pub end_synth_units: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DepthInterval {
+    pub start_time: String,
+    pub end_time: String,
+    pub asset_depth: String,
+    pub rune_depth: String,
+    pub asset_price: String,
+    #[serde(rename = "assetPriceUSD")]
+    pub asset_price_usd: String,
+    pub liquidity_units: String,
+    pub members_count: String,
+    pub synth_units: String,
+    pub synth_supply: String,
+    pub units: String,
+    pub luvi: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SwapInterval {
+    pub start_time: String,
+    pub end_time: String,
+    pub to_asset_count: String,
+    pub to_rune_count: String,
+    pub to_trade_count: String,
+    pub from_trade_count: String,
+    pub synth_mint_count: String,
+    pub synth_redeem_count: String,
+    pub total_count: String,
+    pub to_asset_volume: String,
+    pub to_rune_volume: String,
+    pub to_trade_volume: String,
+    pub from_trade_volume: String,
+    pub synth_mint_volume: String,
+    pub synth_redeem_volume: String,
+    pub total_volume: String,
+    #[serde(rename = "toAssetVolumeUSD")]
+    pub to_asset_volume_usd: String,
+    #[serde(rename = "toRuneVolumeUSD")]
+    pub to_rune_volume_usd: String,
+    #[serde(rename = "toTradeVolumeUSD")]
+    pub to_trade_volume_usd: String,
+    #[serde(rename = "fromTradeVolumeUSD")]
+    pub from_trade_volume_usd: String,
+    #[serde(rename = "synthMintVolumeUSD")]
+    pub synth_mint_volume_usd: String,
+    #[serde(rename = "synthRedeemVolumeUSD")]
+    pub synth_redeem_volume_usd: String,
+    #[serde(rename = "totalVolumeUSD")]
+    pub total_volume_usd: String,
+    pub to_asset_fees: String,
+    pub to_rune_fees: String,
+    pub to_trade_fees: String,
+    pub from_trade_fees: String,
+    pub synth_mint_fees: String,
+    pub synth_redeem_fees: String,
+    pub total_fees: String,
+    pub to_asset_average_slip: String,
+    pub to_rune_average_slip: String,
+    pub to_trade_average_slip: String,
+    pub from_trade_average_slip: String,
+    pub synth_mint_average_slip: String,
+    pub synth_redeem_average_slip: String,
+    pub average_slip: String,
+    #[serde(rename = "runePriceUSD")]
+    pub rune_price_usd: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EarningsInterval {
+    pub start_time: String,
+    pub end_time: String,
+    pub liquidity_fees: String,
+    pub block_rewards: String,
+    pub earnings: String,
+    pub bonding_earnings: String,
+    pub liquidity_earnings: String,
+    pub avg_node_count: String,
+    #[serde(rename = "runePriceUSD")]
+    pub rune_price_usd: String,
+    pub pools: Vec<EarningsPool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EarningsPool {
+    pub pool: String,
+    pub asset_liquidity_fees: String,
+    pub rune_liquidity_fees: String,
+    pub total_liquidity_fees_rune: String,
+    pub saver_earning: String,
+    pub rewards: String,
+    pub earnings: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunePoolMeta {
+    pub start_time: String,
+    pub end_time: String,
+    pub start_units: String,
+    pub start_count: String,
+    pub end_units: String,
+    pub end_count: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RunePoolInterval {
+    pub start_time: String,
+    pub end_time: String,
+    pub count: String,
+    pub units: String,
+}
+
+// `pool` and `interval` are not part of the Midgard payload; callers fill them in.
+impl TryFrom<&DepthInterval> for DepthPrice {
+    type Error = DecodeError;
+
+    fn try_from(item: &DepthInterval) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: 0, // Assigned by DB
+            pool: String::new(),
+            interval: String::new(),
+            start_time: parse_time("startTime", &item.start_time, &item.start_time)?,
+            end_time: parse_time("endTime", &item.start_time, &item.end_time)?,
+            is_partial: is_open(&item.end_time),
+            asset_depth: parse_field("assetDepth", &item.start_time, &item.asset_depth)?,
+            rune_depth: parse_field("runeDepth", &item.start_time, &item.rune_depth)?,
+            asset_price: parse_field("assetPrice", &item.start_time, &item.asset_price)?,
+            asset_price_usd: parse_field("assetPriceUSD", &item.start_time, &item.asset_price_usd)?,
+            liquidity_units: parse_field("liquidityUnits", &item.start_time, &item.liquidity_units)?,
+            members_count: parse_field("membersCount", &item.start_time, &item.members_count)?,
+            synth_units: parse_field("synthUnits", &item.start_time, &item.synth_units)?,
+            synth_supply: parse_field("synthSupply", &item.start_time, &item.synth_supply)?,
+            units: parse_field("units", &item.start_time, &item.units)?,
+            luvi: parse_field("luvi", &item.start_time, &item.luvi)?,
+        })
+    }
+}
+
+impl TryFrom<&SwapInterval> for Swap {
+    type Error = DecodeError;
+
+    fn try_from(item: &SwapInterval) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: 0, // Assigned by DB
+            pool: String::new(),
+            interval: String::new(),
+            start_time: parse_time("startTime", &item.start_time, &item.start_time)?,
+            end_time: parse_time("endTime", &item.start_time, &item.end_time)?,
+            is_partial: is_open(&item.end_time),
+            to_asset_count: parse_field("toAssetCount", &item.start_time, &item.to_asset_count)?,
+            to_rune_count: parse_field("toRuneCount", &item.start_time, &item.to_rune_count)?,
+            to_trade_count: parse_field("toTradeCount", &item.start_time, &item.to_trade_count)?,
+            from_trade_count: parse_field("fromTradeCount", &item.start_time, &item.from_trade_count)?,
+            synth_mint_count: parse_field("synthMintCount", &item.start_time, &item.synth_mint_count)?,
+            synth_redeem_count: parse_field("synthRedeemCount", &item.start_time, &item.synth_redeem_count)?,
+            total_count: parse_field("totalCount", &item.start_time, &item.total_count)?,
+            to_asset_volume: parse_field("toAssetVolume", &item.start_time, &item.to_asset_volume)?,
+            to_rune_volume: parse_field("toRuneVolume", &item.start_time, &item.to_rune_volume)?,
+            to_trade_volume: parse_field("toTradeVolume", &item.start_time, &item.to_trade_volume)?,
+            from_trade_volume: parse_field("fromTradeVolume", &item.start_time, &item.from_trade_volume)?,
+            synth_mint_volume: parse_field("synthMintVolume", &item.start_time, &item.synth_mint_volume)?,
+            synth_redeem_volume: parse_field("synthRedeemVolume", &item.start_time, &item.synth_redeem_volume)?,
+            total_volume: parse_field("totalVolume", &item.start_time, &item.total_volume)?,
+            to_asset_volume_usd: parse_field("toAssetVolumeUSD", &item.start_time, &item.to_asset_volume_usd)?,
+            to_rune_volume_usd: parse_field("toRuneVolumeUSD", &item.start_time, &item.to_rune_volume_usd)?,
+            to_trade_volume_usd: parse_field("toTradeVolumeUSD", &item.start_time, &item.to_trade_volume_usd)?,
+            from_trade_volume_usd: parse_field("fromTradeVolumeUSD", &item.start_time, &item.from_trade_volume_usd)?,
+            synth_mint_volume_usd: parse_field("synthMintVolumeUSD", &item.start_time, &item.synth_mint_volume_usd)?,
+            synth_redeem_volume_usd: parse_field("synthRedeemVolumeUSD", &item.start_time, &item.synth_redeem_volume_usd)?,
+            total_volume_usd: parse_field("totalVolumeUSD", &item.start_time, &item.total_volume_usd)?,
+            to_asset_fees: parse_field("toAssetFees", &item.start_time, &item.to_asset_fees)?,
+            to_rune_fees: parse_field("toRuneFees", &item.start_time, &item.to_rune_fees)?,
+            to_trade_fees: parse_field("toTradeFees", &item.start_time, &item.to_trade_fees)?,
+            from_trade_fees: parse_field("fromTradeFees", &item.start_time, &item.from_trade_fees)?,
+            synth_mint_fees: parse_field("synthMintFees", &item.start_time, &item.synth_mint_fees)?,
+            synth_redeem_fees: parse_field("synthRedeemFees", &item.start_time, &item.synth_redeem_fees)?,
+            total_fees: parse_field("totalFees", &item.start_time, &item.total_fees)?,
+            to_asset_average_slip: parse_field("toAssetAverageSlip", &item.start_time, &item.to_asset_average_slip)?,
+            to_rune_average_slip: parse_field("toRuneAverageSlip", &item.start_time, &item.to_rune_average_slip)?,
+            to_trade_average_slip: parse_field("toTradeAverageSlip", &item.start_time, &item.to_trade_average_slip)?,
+            from_trade_average_slip: parse_field("fromTradeAverageSlip", &item.start_time, &item.from_trade_average_slip)?,
+            synth_mint_average_slip: parse_field("synthMintAverageSlip", &item.start_time, &item.synth_mint_average_slip)?,
+            synth_redeem_average_slip: parse_field("synthRedeemAverageSlip", &item.start_time, &item.synth_redeem_average_slip)?,
+            average_slip: parse_field("averageSlip", &item.start_time, &item.average_slip)?,
+            rune_price_usd: parse_field("runePriceUSD", &item.start_time, &item.rune_price_usd)?,
+        })
+    }
+}
+
+// `interval` is filled in by the caller.
+impl TryFrom<&EarningsInterval> for Earnings {
+    type Error = DecodeError;
+
+    fn try_from(item: &EarningsInterval) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: 0, // Assigned by DB
+            interval: String::new(),
+            start_time: parse_time("startTime", &item.start_time, &item.start_time)?,
+            end_time: parse_time("endTime", &item.start_time, &item.end_time)?,
+            is_partial: is_open(&item.end_time),
+            liquidity_fees: parse_field("liquidityFees", &item.start_time, &item.liquidity_fees)?,
+            block_rewards: parse_field("blockRewards", &item.start_time, &item.block_rewards)?,
+            earnings: parse_field("earnings", &item.start_time, &item.earnings)?,
+            bonding_earnings: parse_field("bondingEarnings", &item.start_time, &item.bonding_earnings)?,
+            liquidity_earnings: parse_field("liquidityEarnings", &item.start_time, &item.liquidity_earnings)?,
+            avg_node_count: parse_field("avgNodeCount", &item.start_time, &item.avg_node_count)?,
+            rune_price_usd: parse_field("runePriceUSD", &item.start_time, &item.rune_price_usd)?,
+            pools: None, // Stored separately in pool_earnings
+        })
+    }
+}
+
+// The parent interval's startTime names the entry in errors; `earnings_history_id` is filled in by the caller.
+impl TryFrom<(&EarningsInterval, &EarningsPool)> for PoolEarning {
+    type Error = DecodeError;
+
+    fn try_from((interval, item): (&EarningsInterval, &EarningsPool)) -> Result<Self, Self::Error> {
+        let start_time = &interval.start_time;
+        Ok(Self {
+            id: 0, // Assigned by DB
+            earnings_history_id: 0,
+            pool: item.pool.clone(),
+            asset_liquidity_fees: parse_field("pools.assetLiquidityFees", start_time, &item.asset_liquidity_fees)?,
+            rune_liquidity_fees: parse_field("pools.runeLiquidityFees", start_time, &item.rune_liquidity_fees)?,
+            total_liquidity_fees_rune: parse_field("pools.totalLiquidityFeesRune", start_time, &item.total_liquidity_fees_rune)?,
+            saver_earning: parse_field("pools.saverEarning", start_time, &item.saver_earning)?,
+            rewards: parse_field("pools.rewards", start_time, &item.rewards)?,
+            earnings: parse_field("pools.earnings", start_time, &item.earnings)?,
+        })
+    }
+}
+
+// `interval` is filled in by the caller.
+impl TryFrom<&RunePoolInterval> for RunePool {
+    type Error = DecodeError;
+
+    fn try_from(item: &RunePoolInterval) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: 0, // Assigned by DB
+            interval: String::new(),
+            start_time: parse_time("startTime", &item.start_time, &item.start_time)?,
+            end_time: parse_time("endTime", &item.start_time, &item.end_time)?,
+            is_partial: is_open(&item.end_time),
+            count: parse_field("count", &item.start_time, &item.count)?,
+            units: parse_field("units", &item.start_time, &item.units)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn runepool_response(interval: serde_json::Value) -> serde_json::Value {
+        json!({
+            "meta": {
+                "startTime": "1700000000", "endTime": "1700086400",
+                "startUnits": "1", "startCount": "1", "endUnits": "2", "endCount": "2"
+            },
+            "intervals": [interval]
+        })
+    }
+
+    #[test]
+    fn converts_typed_interval() {
+        let history: RunePoolHistory = decode_history(runepool_response(json!({
+            "startTime": "1700000000", "endTime": "1700086400", "count": "12", "units": "3400"
+        }))).unwrap();
+        let runepool = RunePool::try_from(&history.intervals[0]).unwrap();
+        assert_eq!(runepool.count, 12);
+        assert_eq!(runepool.units, 3400);
+        assert_eq!(runepool.start_time.timestamp(), 1700000000);
+        assert!(!runepool.is_partial);
+    }
+
+    #[test]
+    fn flags_interval_ending_in_the_future_as_partial() {
+        let end_time = (Utc::now().timestamp() + 3600).to_string();
+        let history: RunePoolHistory = decode_history(runepool_response(json!({
+            "startTime": "1700000000", "endTime": end_time, "count": "12", "units": "3400"
+        }))).unwrap();
+        assert!(RunePool::try_from(&history.intervals[0]).unwrap().is_partial);
+    }
+
+    #[test]
+    fn missing_field_names_field_and_interval() {
+        let err = decode_history::<RunePoolMeta, RunePoolInterval>(runepool_response(json!({
+            "startTime": "1700000000", "endTime": "1700086400", "units": "3400"
+        }))).unwrap_err().to_string();
+        assert!(err.contains("interval 0 (startTime 1700000000)"), "{}", err);
+        assert!(err.contains("missing field `count`"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_field_is_rejected() {
+        let err = decode_history::<RunePoolMeta, RunePoolInterval>(runepool_response(json!({
+            "startTime": "1700000000", "endTime": "1700086400", "count": "12", "units": "3400", "memberCount": "12"
+        }))).unwrap_err().to_string();
+        assert!(err.contains("unknown field `memberCount`"), "{}", err);
+    }
+
+    #[test]
+    fn unparsable_value_names_field_and_interval() {
+        let history: RunePoolHistory = decode_history(runepool_response(json!({
+            "startTime": "1700000000", "endTime": "1700086400", "count": "twelve", "units": "3400"
+        }))).unwrap();
+        let err = RunePool::try_from(&history.intervals[0]).unwrap_err().to_string();
+        assert_eq!(err, "invalid value \"twelve\" for field `count` in Midgard interval starting 1700000000");
+    }
+}
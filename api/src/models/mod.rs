@@ -3,22 +3,39 @@ pub mod swap;
 pub mod earnings;
 pub mod pool_earning;
 pub mod rune_pool;
+pub mod interval;
+pub mod ingestion;
+pub mod aggregate;
+pub mod candle;
+pub mod pool_yield;
+pub mod lp_return;
+pub mod midgard;
 
 pub use depth_price::DepthPrice;
 pub use swap::Swap;
 pub use earnings::Earnings;
-pub use pool_earning::PoolEarning;
+pub use pool_earning::{PoolEarning, PoolEarningHistory};
 pub use rune_pool::RunePool;
+pub use interval::Interval;
+pub use ingestion::{IngestionStatus, IntervalGap};
+pub use aggregate::{Aggregate, AggregateParams};
+pub use candle::{Candle, CandleParams};
+pub use pool_yield::{PoolYield, YieldParams, YieldWindow};
+pub use lp_return::{LpReturn, LpReturnParams};
 
-use serde::{Serialize, Deserialize, Deserializer};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use std::str::FromStr;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PoolActivity {
     pub pool: String,
     pub asset_depth: i64,
     pub rune_depth: i64,
-    pub asset_price: f64,
+    pub asset_price: Decimal,
     pub to_asset_volume: i64,
     pub total_fees: i64,
     pub total_volume_usd: i64,
@@ -46,13 +63,236 @@ impl From<tokio_postgres::Row> for PoolActivity {
 pub struct QueryParams {
     #[serde(default, deserialize_with = "deserialize_date_range")]
     pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    #[serde(default)]
+    pub interval: Interval,
+    pub pool: Option<String>,
+    // Comma-separated, e.g. "BTC.BTC,ETH.ETH"; combined with `pool`.
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub pools: Option<Vec<String>>,
+    // Leaves out the still-open newest interval, whose values change until it closes.
+    #[serde(default)]
+    pub exclude_partial: bool,
     pub start_date: Option<DateTime<Utc>>,
     pub end_date: Option<DateTime<Utc>>,
     pub liquidity_gt: Option<i64>,
+    // `column[op]=value` filters, which serde cannot name ahead of time; see `FieldFilter::parse_query`.
+    #[serde(skip)]
+    pub filters: Vec<FieldFilter>,
     pub sort_by: Option<String>,
-    pub order: Option<String>,
-    pub page: Option<i64>,
+    pub order: Option<SortOrder>,
+    // Offset paging, kept for existing clients; `cursor` is cheaper and stable while rows arrive.
+    pub page: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_cursor")]
+    pub cursor: Option<Cursor>,
     pub limit: Option<i64>,
+    // Answers with the bare array of rows instead of the paginated envelope.
+    #[serde(default)]
+    pub legacy: bool,
+    // Related rows to nest in each result, e.g. "pools" for earnings-history.
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub include: Option<Vec<String>>,
+}
+
+impl QueryParams {
+    // Every pool named by `pool` or `pools`; empty when neither is given.
+    pub fn requested_pools(&self) -> Vec<String> {
+        self.pool.iter().chain(self.pools.iter().flatten()).cloned().collect()
+    }
+
+    pub fn includes(&self, name: &str) -> bool {
+        self.include.iter().flatten().any(|include| include == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Between,
+}
+
+impl FilterOp {
+    // The SQL comparison for every operator but `between`, which takes two values.
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            FilterOp::Eq => "=",
+            FilterOp::Ne => "<>",
+            FilterOp::Gt => ">",
+            FilterOp::Gte => ">=",
+            FilterOp::Lt => "<",
+            FilterOp::Lte => "<=",
+            FilterOp::Between => "BETWEEN",
+        }
+    }
+}
+
+impl FromStr for FilterOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eq" => Ok(FilterOp::Eq),
+            "ne" => Ok(FilterOp::Ne),
+            "gt" => Ok(FilterOp::Gt),
+            "gte" => Ok(FilterOp::Gte),
+            "lt" => Ok(FilterOp::Lt),
+            "lte" => Ok(FilterOp::Lte),
+            "between" => Ok(FilterOp::Between),
+            _ => Err(format!("unknown filter operator `{}`; expected eq, ne, gt, gte, lt, lte or between", s)),
+        }
+    }
+}
+
+// A value filter on one numeric column, written `column[op]=value` in the query string, or
+// `column[between]=low,high`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldFilter {
+    pub column: String,
+    pub op: FilterOp,
+    pub values: Vec<Decimal>,
+}
+
+impl FieldFilter {
+    // Picks the `column[op]=value` pairs out of a raw query string and ignores every other
+    // parameter. Column names are checked later, against the queried table.
+    pub fn parse_query(query: &str) -> Result<Vec<FieldFilter>, String> {
+        url::form_urlencoded::parse(query.as_bytes())
+            .filter_map(|(key, value)| {
+                let (column, op) = key.strip_suffix(']')?.split_once('[')?;
+                Some(Self::parse(column, op, &value))
+            })
+            .collect()
+    }
+
+    fn parse(column: &str, op: &str, value: &str) -> Result<FieldFilter, String> {
+        let name = format!("{}[{}]", column, op);
+        let op: FilterOp = op.parse()?;
+        let values = value.split(',')
+            .map(|v| Decimal::from_str(v.trim()).map_err(|_| format!("{} expects a number, got `{}`", name, v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected = if op == FilterOp::Between { 2 } else { 1 };
+        if values.len() != expected {
+            return Err(format!("{} takes {} comma-separated value(s), got {}", name, expected, values.len()));
+        }
+        Ok(FieldFilter { column: column.to_string(), op, values })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    #[serde(alias = "ASC")]
+    Asc,
+    #[serde(alias = "DESC")]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+// A position in a history ordered by (start_time, id), handed to clients as an opaque token. `Next`
+// continues after the row it names, `Prev` returns the rows before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub direction: CursorDirection,
+    pub start_time: DateTime<Utc>,
+    pub id: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorDirection {
+    Next,
+    Prev,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            CursorDirection::Next => "n",
+            CursorDirection::Prev => "p",
+        };
+        URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", direction, self.start_time.timestamp_micros(), self.id))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || "invalid cursor; pass a `next` or `prev` cursor from an earlier page unchanged".to_string();
+        let decoded = URL_SAFE_NO_PAD.decode(s).ok().and_then(|bytes| String::from_utf8(bytes).ok()).ok_or_else(invalid)?;
+        let mut parts = decoded.splitn(3, ':');
+        let direction = match parts.next() {
+            Some("n") => CursorDirection::Next,
+            Some("p") => CursorDirection::Prev,
+            _ => return Err(invalid()),
+        };
+        let start_time = parts.next().and_then(|micros| micros.parse().ok()).and_then(DateTime::from_timestamp_micros).ok_or_else(invalid)?;
+        let id = parts.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?;
+        Ok(Cursor { direction, start_time, id })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+// One page of a history, with cursors to the pages either side when the order allows them.
+// `page` is None when the page was reached by cursor.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: Option<i64>,
+    // After capping, so it may be less than the `limit` asked for.
+    pub limit: i64,
+    pub total_count: i64,
+    pub has_more: bool,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+// The body history endpoints answer with unless `legacy=true` asks for the bare array.
+#[derive(Debug, Serialize)]
+pub struct Envelope<T> {
+    pub data: Vec<T>,
+    pub page: Option<i64>,
+    pub limit: i64,
+    pub total_count: i64,
+    pub has_more: bool,
+    pub links: Links,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Links {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+fn deserialize_cursor<'de, D>(deserializer: D) -> Result<Option<Cursor>, D::Error>
+where D: Deserializer<'de> {
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    s.map(|s| s.parse().map_err(serde::de::Error::custom)).transpose()
+}
+
+fn deserialize_list<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where D: Deserializer<'de> {
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    Ok(s.map(|s| s.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()))
 }
 
 fn deserialize_date_range<'de, D>(deserializer: D) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, D::Error>
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
 use tokio_postgres::Row;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,4 +29,42 @@ impl From<Row> for PoolEarning {
             earnings: row.get("earnings"),
         }
     }
-}
\ No newline at end of file
+}
+
+// A pool's share of one earnings interval, with the interval's timestamps from earnings_history.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolEarningHistory {
+    pub id: i32,
+    pub earnings_history_id: i32,
+    pub pool: String,
+    pub interval: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub is_partial: bool,
+    pub asset_liquidity_fees: i64,
+    pub rune_liquidity_fees: i64,
+    pub total_liquidity_fees_rune: i64,
+    pub saver_earning: i64,
+    pub rewards: i64,
+    pub earnings: i64,
+}
+
+impl From<Row> for PoolEarningHistory {
+    fn from(row: Row) -> Self {
+        Self {
+            id: row.get("id"),
+            earnings_history_id: row.get("earnings_history_id"),
+            pool: row.get("pool"),
+            interval: row.get("interval"),
+            start_time: row.get("start_time"),
+            end_time: row.get("end_time"),
+            is_partial: row.get("is_partial"),
+            asset_liquidity_fees: row.get("asset_liquidity_fees"),
+            rune_liquidity_fees: row.get("rune_liquidity_fees"),
+            total_liquidity_fees_rune: row.get("total_liquidity_fees_rune"),
+            saver_earning: row.get("saver_earning"),
+            rewards: row.get("rewards"),
+            earnings: row.get("earnings"),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use tokio_postgres::Row;
+use super::deserialize_list;
+
+#[derive(Debug, Deserialize)]
+pub struct YieldParams {
+    #[serde(default = "default_windows", deserialize_with = "deserialize_windows")]
+    pub windows: Vec<YieldWindow>,
+}
+
+fn default_windows() -> Vec<YieldWindow> {
+    YieldWindow::ALL.to_vec()
+}
+
+// A lookback ending at the pool's latest closed day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YieldWindow {
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl YieldWindow {
+    pub const ALL: [YieldWindow; 4] = [YieldWindow::Week, YieldWindow::Month, YieldWindow::Quarter, YieldWindow::Year];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            YieldWindow::Week => "7d",
+            YieldWindow::Month => "30d",
+            YieldWindow::Quarter => "90d",
+            YieldWindow::Year => "365d",
+        }
+    }
+
+    pub fn days(self) -> i32 {
+        match self {
+            YieldWindow::Week => 7,
+            YieldWindow::Month => 30,
+            YieldWindow::Quarter => 90,
+            YieldWindow::Year => 365,
+        }
+    }
+}
+
+impl FromStr for YieldWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        YieldWindow::ALL.into_iter()
+            .find(|window| window.as_str() == s)
+            .ok_or_else(|| format!("unknown window '{}'; expected 7d, 30d, 90d or 365d", s))
+    }
+}
+
+fn deserialize_windows<'de, D>(deserializer: D) -> Result<Vec<YieldWindow>, D::Error>
+where D: Deserializer<'de> {
+    let windows = deserialize_list(deserializer)?.unwrap_or_default();
+    if windows.is_empty() {
+        return Ok(default_windows());
+    }
+    windows.iter().map(|window| window.parse().map_err(serde::de::Error::custom)).collect()
+}
+
+// Yield of one pool over one window. `apr` is the pool's earnings over its average depth valued in
+// RUNE (rune_depth plus asset_depth at asset_price), annualised over the days with earnings; `apy`
+// compounds it daily. The `luvi_*` fields follow Midgard: the growth of the liquidity unit value
+// index between the first and last day, annualised over the days between them. Rates are
+// fractions (0.12 is 12%) and are null when the window holds too little data.
+#[derive(Debug, Serialize)]
+pub struct PoolYield {
+    pub pool: String,
+    pub window: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub days: i64,
+    pub earnings: Option<Decimal>,
+    pub avg_depth_rune: Option<Decimal>,
+    pub apr: Option<Decimal>,
+    pub apy: Option<Decimal>,
+    pub luvi_start: Option<Decimal>,
+    pub luvi_end: Option<Decimal>,
+    pub luvi_growth: Option<Decimal>,
+    pub luvi_apr: Option<Decimal>,
+    pub luvi_apy: Option<Decimal>,
+}
+
+impl From<Row> for PoolYield {
+    fn from(row: Row) -> Self {
+        Self {
+            pool: row.get("pool"),
+            window: row.get("window"),
+            window_start: row.get("window_start"),
+            window_end: row.get("window_end"),
+            days: row.get("days"),
+            earnings: row.get("earnings"),
+            avg_depth_rune: row.get("avg_depth_rune"),
+            apr: row.get("apr"),
+            apy: row.get("apy"),
+            luvi_start: row.get("luvi_start"),
+            luvi_end: row.get("luvi_end"),
+            luvi_growth: row.get("luvi_growth"),
+            luvi_apr: row.get("luvi_apr"),
+            luvi_apy: row.get("luvi_apy"),
+        }
+    }
+}
@@ -5,8 +5,11 @@ use tokio_postgres::Row;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RunePool {
     pub id: i32,
+    pub interval: String,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    // The interval had not closed yet when it was last fetched.
+    pub is_partial: bool,
     pub count: i64,
     pub units: i64,
 }
@@ -15,8 +18,10 @@ impl From<Row> for RunePool {
     fn from(row: Row) -> Self {
         Self {
             id: row.get("id"),
+            interval: row.get("interval"),
             start_time: row.get("start_time"),
             end_time: row.get("end_time"),
+            is_partial: row.get("is_partial"),
             count: row.get("count"),
             units: row.get("units"),
         }
@@ -1,13 +1,17 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use tokio_postgres::Row;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Swap {
     pub id: i32,
     pub pool: String,
+    pub interval: String,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
+    // The interval had not closed yet when it was last fetched.
+    pub is_partial: bool,
     pub to_asset_count: i64,
     pub to_rune_count: i64,
     pub to_trade_count: i64,
@@ -36,14 +40,14 @@ pub struct Swap {
     pub synth_mint_fees: i64,
     pub synth_redeem_fees: i64,
     pub total_fees: i64,
-    pub to_asset_average_slip: f64,
-    pub to_rune_average_slip: f64,
-    pub to_trade_average_slip: f64,
-    pub from_trade_average_slip: f64,
-    pub synth_mint_average_slip: f64,
-    pub synth_redeem_average_slip: f64,
-    pub average_slip: f64,
-    pub rune_price_usd: f64,
+    pub to_asset_average_slip: Decimal,
+    pub to_rune_average_slip: Decimal,
+    pub to_trade_average_slip: Decimal,
+    pub from_trade_average_slip: Decimal,
+    pub synth_mint_average_slip: Decimal,
+    pub synth_redeem_average_slip: Decimal,
+    pub average_slip: Decimal,
+    pub rune_price_usd: Decimal,
 }
 
 impl From<Row> for Swap {
@@ -51,8 +55,10 @@ impl From<Row> for Swap {
         Self {
             id: row.get("id"),
             pool: row.get("pool"),
+            interval: row.get("interval"),
             start_time: row.get("start_time"),
             end_time: row.get("end_time"),
+            is_partial: row.get("is_partial"),
             to_asset_count: row.get("to_asset_count"),
             to_rune_count: row.get("to_rune_count"),
             to_trade_count: row.get("to_trade_count"),
@@ -0,0 +1,99 @@
+use actix_web::dev::ServiceRequest;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use log::error;
+use serde::Serialize;
+use thiserror::Error;
+use crate::db::query::QueryError;
+
+tokio::task_local! {
+    // The id of the request being handled, set by the middleware in `config`.
+    pub static REQUEST_ID: String;
+}
+
+// The caller's X-Request-Id when it sent a usable one, otherwise a fresh id.
+pub fn request_id(req: &ServiceRequest) -> String {
+    req.headers().get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64)
+        .map(String::from)
+        .unwrap_or_else(|| format!("{:016x}", fastrand::u64(..)))
+}
+
+#[derive(Debug, Error)]
+pub enum ApiError {
+    // The request could not be read, e.g. a malformed `date_range` or filter value.
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    NotFound(String),
+    // Readable, but asks for something the endpoint does not offer, e.g. an unknown sort column.
+    #[error(transparent)]
+    Unprocessable(#[from] QueryError),
+    #[error("database unavailable: {0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message: String,
+    request_id: &'a str,
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Unprocessable(_) => "unprocessable_query",
+            ApiError::Unavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+// Services report failures as boxed errors; the query builder's are the caller's fault, a pool or
+// connection failure means the database is down, anything else is ours.
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        let e = match e.downcast::<QueryError>() {
+            Ok(e) => return ApiError::Unprocessable(*e),
+            Err(e) => e,
+        };
+        let closed = e.downcast_ref::<tokio_postgres::Error>().is_some_and(|e| e.is_closed());
+        if closed || e.is::<deadpool_postgres::PoolError>() {
+            ApiError::Unavailable(e.to_string())
+        } else {
+            ApiError::Internal(e.to_string())
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Server-side failures are logged in full under the request id and answered with a generic
+    // message, so database details do not leak to clients.
+    fn error_response(&self) -> HttpResponse {
+        let request_id = REQUEST_ID.try_with(Clone::clone).unwrap_or_else(|_| format!("{:016x}", fastrand::u64(..)));
+        let status = self.status_code();
+        let message = if status.is_server_error() {
+            error!("Request {} failed: {}", request_id, self);
+            status.canonical_reason().unwrap_or("Error").to_string()
+        } else {
+            self.to_string()
+        };
+        HttpResponse::build(status).json(ErrorBody { code: self.code(), message, request_id: &request_id })
+    }
+}
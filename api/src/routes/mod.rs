@@ -1,44 +1,210 @@
-use actix_web::{web, HttpResponse};
-use crate::models::QueryParams;
+use actix_web::{dev::{Payload, Service}, http::header::{HeaderName, HeaderValue}, web, FromRequest, HttpRequest, HttpResponse};
+use futures::future::{ready, Ready};
+use crate::models::{AggregateParams, CandleParams, LpReturnParams, YieldParams, Envelope, FieldFilter, Links, Page, QueryParams};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Serialize;
 use crate::services::DepthService;
+use error::{request_id, ApiError, REQUEST_ID};
 
-pub async fn get_depth_history(query: web::Query<QueryParams>, service: web::Data<DepthService>) -> HttpResponse {
-    match service.get_depths(&query).await {
-        Ok(depths) => HttpResponse::Ok().json(depths),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+pub mod error;
+
+// `web::Query` plus the `column[op]=value` filters, which serde leaves alone.
+impl FromRequest for QueryParams {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let parse = || -> Result<QueryParams, ApiError> {
+            let mut params = web::Query::<QueryParams>::from_query(req.query_string())
+                .map_err(|e| ApiError::BadRequest(e.to_string()))?
+                .into_inner();
+            params.filters = FieldFilter::parse_query(req.query_string()).map_err(ApiError::BadRequest)?;
+            Ok(params)
+        };
+        ready(parse())
     }
 }
 
-pub async fn get_swaps_history(query: web::Query<QueryParams>, service: web::Data<DepthService>) -> HttpResponse {
-    match service.get_swaps(&query).await {
-        Ok(swaps) => HttpResponse::Ok().json(swaps),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+// Answers with the page envelope, or the bare array when `legacy=true`. Cursors are sent as
+// headers either way.
+fn page_response<T: Serialize>(req: &HttpRequest, params: &QueryParams, page: Page<T>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    if let Some(next) = &page.next {
+        response.insert_header(("X-Next-Cursor", next.as_str()));
+    }
+    if let Some(prev) = &page.prev {
+        response.insert_header(("X-Prev-Cursor", prev.as_str()));
     }
+    if params.legacy {
+        return response.json(page.items);
+    }
+
+    // Clients paging by number keep paging by number; everyone else follows cursors.
+    let by_number = params.page.is_some() || (page.next.is_none() && page.prev.is_none());
+    let (next, prev) = match page.page {
+        Some(number) if by_number => (
+            page.has_more.then(|| page_link(req, "page", &(number + 1).to_string())),
+            (number > 0).then(|| page_link(req, "page", &(number - 1).to_string())),
+        ),
+        _ => (
+            page.next.as_ref().map(|cursor| page_link(req, "cursor", cursor)),
+            page.prev.as_ref().map(|cursor| page_link(req, "cursor", cursor)),
+        ),
+    };
+    response.json(Envelope {
+        data: page.items,
+        page: page.page,
+        limit: page.limit,
+        total_count: page.total_count,
+        has_more: page.has_more,
+        links: Links { current: req.uri().to_string(), next, prev },
+    })
 }
 
-pub async fn get_earnings_history(query: web::Query<QueryParams>, service: web::Data<DepthService>) -> HttpResponse {
-    match service.get_earnings(&query).await {
-        Ok(earnings) => HttpResponse::Ok().json(earnings),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+// The request's own path and query with `cursor`/`page` replaced by `key=value`.
+fn page_link(req: &HttpRequest, key: &str, value: &str) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    for (k, v) in url::form_urlencoded::parse(req.query_string().as_bytes()) {
+        if k != "cursor" && k != "page" {
+            query.append_pair(&k, &v);
+        }
     }
+    query.append_pair(key, value);
+    format!("{}?{}", req.path(), query.finish())
 }
 
-pub async fn get_runepool_history(query: web::Query<QueryParams>, service: web::Data<DepthService>) -> HttpResponse {
-    match service.get_runepools(&query).await {
-        Ok(runepools) => HttpResponse::Ok().json(runepools),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
-    }
+pub async fn get_depth_history(req: HttpRequest, query: QueryParams, service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    let page = service.get_depths(&query).await?;
+    Ok(page_response(&req, &query, page))
+}
+
+pub async fn get_swaps_history(req: HttpRequest, query: QueryParams, service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    let page = service.get_swaps(&query).await?;
+    Ok(page_response(&req, &query, page))
+}
+
+pub async fn get_earnings_history(req: HttpRequest, query: QueryParams, service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    let page = service.get_earnings(&query).await?;
+    Ok(page_response(&req, &query, page))
+}
+
+pub async fn get_runepool_history(req: HttpRequest, query: QueryParams, service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    let page = service.get_runepools(&query).await?;
+    Ok(page_response(&req, &query, page))
 }
 
 pub async fn get_pool_activity(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: QueryParams,
+    service: web::Data<DepthService>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = path.into_inner();
+    let page = service.get_pool_activity(&pool, &query).await?;
+    // An empty page is only an error when the pool has never had any data at all.
+    if page.total_count == 0 && !service.pool_exists(&pool).await? {
+        return Err(ApiError::NotFound(format!("no data for pool {}", pool)));
+    }
+    Ok(page_response(&req, &query, page))
+}
+
+pub async fn get_interval_pool_earnings(path: web::Path<i32>, service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    match service.get_interval_pool_earnings(id).await? {
+        Some(earnings) => Ok(HttpResponse::Ok().json(earnings)),
+        None => Err(ApiError::NotFound(format!("no earnings interval with id {}", id))),
+    }
+}
+
+pub async fn get_pool_earnings(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: QueryParams,
+    service: web::Data<DepthService>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = path.into_inner();
+    let page = service.get_pool_earnings(&pool, &query).await?;
+    if page.total_count == 0 && !service.pool_exists(&pool).await? {
+        return Err(ApiError::NotFound(format!("no data for pool {}", pool)));
+    }
+    Ok(page_response(&req, &query, page))
+}
+
+pub async fn get_aggregates(
+    req: HttpRequest,
+    path: web::Path<String>,
+    aggregate: web::Query<AggregateParams>,
+    query: QueryParams,
+    service: web::Data<DepthService>,
+) -> Result<HttpResponse, ApiError> {
+    let dataset = path.into_inner();
+    match service.get_aggregates(&dataset, &aggregate, &query).await? {
+        Some(page) => Ok(page_response(&req, &query, page)),
+        None => Err(ApiError::NotFound(format!("no dataset {}; expected depth-history, swaps-history, earnings-history or runepool-history", dataset))),
+    }
+}
+
+pub async fn get_candles(
+    req: HttpRequest,
+    path: web::Path<String>,
+    candles: web::Query<CandleParams>,
+    query: QueryParams,
+    service: web::Data<DepthService>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = path.into_inner();
+    match service.get_candles(&pool, &candles, &query).await? {
+        Some(page) => Ok(page_response(&req, &query, page)),
+        None => Err(ApiError::NotFound(format!("no depth history for pool {} at {} or finer", pool, candles.bucket))),
+    }
+}
+
+pub async fn get_pool_yield(
+    path: web::Path<String>,
+    yields: web::Query<YieldParams>,
+    query: QueryParams,
+    service: web::Data<DepthService>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = path.into_inner();
+    match service.get_yields(&pool, &yields, &query).await? {
+        Some(yields) => Ok(HttpResponse::Ok().json(yields)),
+        None => Err(ApiError::NotFound(format!("no closed daily depth history for pool {}", pool))),
+    }
+}
+
+pub async fn get_lp_return(
     path: web::Path<String>,
-    query: web::Query<QueryParams>,
+    params: web::Query<LpReturnParams>,
     service: web::Data<DepthService>,
-) -> HttpResponse {
-    match service.get_pool_activity(path.into_inner(), &query).await {
-        Ok(activity) => HttpResponse::Ok().json(activity),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+) -> Result<HttpResponse, ApiError> {
+    let pool = path.into_inner();
+    let now = Utc::now();
+    let exit = params.exit.unwrap_or(now);
+    if params.entry > now {
+        return Err(ApiError::BadRequest("entry must not be in the future".to_string()));
     }
+    if exit <= params.entry {
+        return Err(ApiError::BadRequest("exit must be later than entry".to_string()));
+    }
+    if params.deposit_usd <= Decimal::ZERO {
+        return Err(ApiError::BadRequest("deposit_usd must be positive".to_string()));
+    }
+    match service.get_lp_return(&pool, &params, exit).await? {
+        Some(lp_return) => Ok(HttpResponse::Ok().json(lp_return)),
+        None => Err(ApiError::NotFound(format!("no depth history for pool {} closed by {}", pool, params.entry.to_rfc3339()))),
+    }
+}
+
+pub async fn get_ingestion_status(service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(service.get_ingestion_status().await?))
+}
+
+pub async fn get_ingestion_gaps(service: web::Data<DepthService>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(service.get_unresolved_gaps().await?))
+}
+
+pub async fn not_found(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::NotFound(format!("no route for {} {}", req.method(), req.path())))
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -48,6 +214,153 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("/swaps-history", web::get().to(get_swaps_history))
             .route("/earnings-history", web::get().to(get_earnings_history))
             .route("/runepool-history", web::get().to(get_runepool_history))
+            .route("/earnings-history/{id}/pools", web::get().to(get_interval_pool_earnings))
             .route("/pool-activity/{pool_id}", web::get().to(get_pool_activity))
+            .route("/ingestion/status", web::get().to(get_ingestion_status))
+            .route("/ingestion/gaps", web::get().to(get_ingestion_gaps))
+            .route("/pools/{pool}/candles", web::get().to(get_candles))
+            .route("/pools/{pool}/earnings", web::get().to(get_pool_earnings))
+            .route("/pools/{pool}/yield", web::get().to(get_pool_yield))
+            .route("/pools/{pool}/returns", web::get().to(get_lp_return))
+            .route("/{dataset}/aggregate", web::get().to(get_aggregates))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
+            .app_data(web::PathConfig::default().error_handler(|e, _| ApiError::NotFound(e.to_string()).into()))
+            .default_service(web::to(not_found))
+            // Every response carries X-Request-Id, and errors quote it in their body.
+            .wrap_fn(|req, srv| {
+                let id = request_id(&req);
+                let response = REQUEST_ID.scope(id.clone(), srv.call(req));
+                async move {
+                    let mut response = response.await?;
+                    if let Ok(value) = HeaderValue::from_str(&id) {
+                        response.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+                    }
+                    Ok(response)
+                }
+            })
     );
-}
\ No newline at end of file
+    cfg.default_service(web::to(not_found));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{insert_test_depths, remove_test_pool, test_depth, test_pool};
+    use crate::models::DepthPrice;
+    use actix_web::{test, App};
+    use chrono::{DateTime, Duration};
+
+    // Status and body of `uri`, sent with a request id every answer must echo.
+    async fn get(uri: &str) -> (u16, serde_json::Value) {
+        let app = test::init_service(App::new().app_data(web::Data::new(DepthService::new(test_pool()))).configure(config)).await;
+        let req = test::TestRequest::get().uri(uri).insert_header(("X-Request-Id", "test-42")).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("x-request-id").unwrap(), "test-42", "{}", uri);
+        (resp.status().as_u16(), test::read_body_json(resp).await)
+    }
+
+    async fn assert_error(uri: &str, status: u16, code: &str) {
+        let (actual, body) = get(uri).await;
+        assert_eq!((actual, body["code"].as_str(), body["request_id"].as_str()), (status, Some(code), Some("test-42")), "{}: {}", uri, body);
+    }
+
+    // Three closed daily rows of `name` from 2003-01-01, with the asset at 1, 2 and 3.
+    async fn seed_days(name: &str) {
+        let pool = test_pool();
+        remove_test_pool(&pool, name).await;
+        let start = DateTime::parse_from_rfc3339("2003-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let depths: Vec<DepthPrice> = (0..3)
+            .map(|day| DepthPrice {
+                asset_price: (day + 1).into(),
+                asset_price_usd: (day + 1).into(),
+                ..test_depth(name, "day", start + Duration::days(day), start + Duration::days(day + 1))
+            })
+            .collect();
+        insert_test_depths(&pool, &depths).await;
+    }
+
+    #[actix_rt::test]
+    async fn wraps_history_in_a_page_envelope() {
+        seed_days("ENVELOPE.TEST").await;
+
+        let (status, body) = get("/api/depth-history?pool=ENVELOPE.TEST&limit=2").await;
+        assert_eq!(status, 200);
+        assert_eq!((body["data"].as_array().map(Vec::len), body["limit"].as_i64()), (Some(2), Some(2)), "{}", body);
+        assert_eq!((body["total_count"].as_i64(), body["has_more"].as_bool()), (Some(3), Some(true)), "{}", body);
+
+        let (_, body) = get("/api/depth-history?pool=ENVELOPE.TEST&limit=500").await;
+        assert_eq!(body["limit"], 100);
+        let (_, body) = get("/api/depth-history?pool=ENVELOPE.TEST&limit=2&legacy=true").await;
+        assert_eq!(body.as_array().map(Vec::len), Some(2), "{}", body);
+        remove_test_pool(&test_pool(), "ENVELOPE.TEST").await;
+    }
+
+    #[actix_rt::test]
+    async fn answers_errors_as_typed_json() {
+        assert_error("/api/depth-history?date_range=yesterday", 400, "bad_request").await;
+        assert_error("/api/depth-history?sort_by=nonsense", 422, "unprocessable_query").await;
+        assert_error("/api/depth-history?page=-5", 400, "bad_request").await;
+        assert_error("/api/pool-activity/NOPE.NOPE", 404, "not_found").await;
+        assert_error("/api/nowhere", 404, "not_found").await;
+    }
+
+    #[actix_rt::test]
+    async fn rejects_unusable_value_filters() {
+        assert_error("/api/swaps-history?average_slip[lt]=low", 400, "bad_request").await;
+        assert_error("/api/swaps-history?liquidity_gt=1", 422, "unprocessable_query").await;
+        assert_error("/api/pools/BTC.BTC/candles?open[gt]=1", 422, "unprocessable_query").await;
+        assert_error("/api/pools/BTC.BTC/yield?liquidity_gt=1", 422, "unprocessable_query").await;
+    }
+
+    #[actix_rt::test]
+    async fn serves_ingestion_status_and_gaps() {
+        for uri in ["/api/ingestion/status", "/api/ingestion/gaps"] {
+            let (status, body) = get(uri).await;
+            assert_eq!(status, 200, "{}", uri);
+            assert!(body.is_array(), "{}: {}", uri, body);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn aggregates_a_dataset_per_bucket() {
+        seed_days("AGGREGATE.TEST").await;
+
+        let (status, body) = get("/api/depth-history/aggregate?pool=AGGREGATE.TEST&fields=asset_price&agg=avg&bucket=month").await;
+        assert_eq!(status, 200);
+        let bucket = &body["data"][0];
+        assert_eq!((bucket["row_count"].as_i64(), bucket["values"]["asset_price"].as_str()), (Some(3), Some("2.0000000000000000")), "{}", body);
+
+        assert_error("/api/nowhere/aggregate?fields=units", 404, "not_found").await;
+        assert_error("/api/swaps-history/aggregate?fields=total_fees&agg=median", 400, "bad_request").await;
+        assert_error("/api/swaps-history/aggregate?fields=pool", 422, "unprocessable_query").await;
+        remove_test_pool(&test_pool(), "AGGREGATE.TEST").await;
+    }
+
+    #[actix_rt::test]
+    async fn answers_unknown_earnings_with_404() {
+        assert_error("/api/earnings-history/0/pools", 404, "not_found").await;
+        assert_error("/api/earnings-history/latest/pools", 404, "not_found").await;
+        assert_error("/api/pools/NOPE.NOPE/earnings", 404, "not_found").await;
+    }
+
+    #[actix_rt::test]
+    async fn rejects_includes_an_endpoint_does_not_offer() {
+        assert_error("/api/depth-history?include=pools", 422, "unprocessable_query").await;
+        assert_error("/api/earnings-history?include=swaps", 422, "unprocessable_query").await;
+    }
+
+    #[actix_rt::test]
+    async fn validates_lp_return_entry_and_exit() {
+        seed_days("RETURNS.TEST").await;
+
+        let (status, body) = get("/api/pools/RETURNS.TEST/returns?entry=2003-01-02T00:00:00Z&exit=2003-01-04T00:00:00Z").await;
+        assert_eq!((status, body["price_ratio"].as_str()), (200, Some("3.00000000")), "{}", body);
+
+        assert_error("/api/pools/RETURNS.TEST/returns", 400, "bad_request").await;
+        assert_error("/api/pools/RETURNS.TEST/returns?entry=2003-01-03T00:00:00Z&exit=2003-01-02T00:00:00Z", 400, "bad_request").await;
+        assert_error("/api/pools/RETURNS.TEST/returns?entry=2999-01-01T00:00:00Z", 400, "bad_request").await;
+        assert_error("/api/pools/RETURNS.TEST/returns?entry=2003-01-02T06:00:00Z&exit=2003-01-02T18:00:00Z", 422, "unprocessable_query").await;
+        assert_error("/api/pools/NOPE.NOPE/returns?entry=2003-01-02T00:00:00Z", 404, "not_found").await;
+        remove_test_pool(&test_pool(), "RETURNS.TEST").await;
+    }
+}
@@ -1,7 +1,10 @@
 use deadpool_postgres::Pool;
 use crate::db::Database;
-use crate::models::{DepthPrice, Swap, Earnings, RunePool, PoolActivity, QueryParams};
+use crate::midgard::MidgardClient;
+use crate::models::{DepthPrice, Swap, Earnings, PoolEarning, PoolEarningHistory, RunePool, PoolActivity, Aggregate, AggregateParams, Candle, CandleParams, PoolYield, YieldParams, LpReturn, LpReturnParams, Page, QueryParams, Interval, IngestionStatus, IntervalGap};
 use crate::fetcher::{fetch_depth_data, fetch_swaps_data, fetch_earnings_data, fetch_runepool_data};
+use crate::fetcher::{backfill_depth_data, backfill_swaps_data, backfill_earnings_data, backfill_runepool_data, repair_gaps};
+use chrono::{DateTime, Utc};
 
 #[derive(Clone)]
 pub struct DepthService {
@@ -13,39 +16,95 @@ impl DepthService {
         Self { db: Database::new(pool) }
     }
 
-    pub async fn get_depths(&self, params: &QueryParams) -> Result<Vec<DepthPrice>, Box<dyn std::error::Error>> {
+    pub async fn get_depths(&self, params: &QueryParams) -> Result<Page<DepthPrice>, Box<dyn std::error::Error>> {
         self.db.find_depths(params).await
     }
 
-    pub async fn get_swaps(&self, params: &QueryParams) -> Result<Vec<Swap>, Box<dyn std::error::Error>> {
+    pub async fn get_swaps(&self, params: &QueryParams) -> Result<Page<Swap>, Box<dyn std::error::Error>> {
         self.db.find_swaps(params).await
     }
 
-    pub async fn get_earnings(&self, params: &QueryParams) -> Result<Vec<Earnings>, Box<dyn std::error::Error>> {
+    pub async fn get_earnings(&self, params: &QueryParams) -> Result<Page<Earnings>, Box<dyn std::error::Error>> {
         self.db.find_earnings(params).await
     }
 
-    pub async fn get_runepools(&self, params: &QueryParams) -> Result<Vec<RunePool>, Box<dyn std::error::Error>> {
+    pub async fn get_runepools(&self, params: &QueryParams) -> Result<Page<RunePool>, Box<dyn std::error::Error>> {
         self.db.find_runepools(params).await
     }
 
-    pub async fn get_pool_activity(&self, pool_id: String, params: &QueryParams) -> Result<Vec<PoolActivity>, Box<dyn std::error::Error>> {
-        self.db.find_pool_activity(&pool_id, params).await
+    pub async fn get_pool_activity(&self, pool_id: &str, params: &QueryParams) -> Result<Page<PoolActivity>, Box<dyn std::error::Error>> {
+        self.db.find_pool_activity(pool_id, params).await
     }
 
-    pub async fn fetch_and_store_depths(&self, client: &reqwest::Client) -> Result<(), Box<dyn std::error::Error>> {
-        fetch_depth_data(&self.db.pool, client).await
+    pub async fn get_interval_pool_earnings(&self, earnings_history_id: i32) -> Result<Option<Vec<PoolEarning>>, Box<dyn std::error::Error>> {
+        self.db.find_interval_pool_earnings(earnings_history_id).await
     }
 
-    pub async fn fetch_and_store_swaps(&self, client: &reqwest::Client) -> Result<(), Box<dyn std::error::Error>> {
-        fetch_swaps_data(&self.db.pool, client).await
+    pub async fn get_pool_earnings(&self, pool_id: &str, params: &QueryParams) -> Result<Page<PoolEarningHistory>, Box<dyn std::error::Error>> {
+        self.db.find_pool_earnings(pool_id, params).await
     }
 
-    pub async fn fetch_and_store_earnings(&self, client: &reqwest::Client) -> Result<(), Box<dyn std::error::Error>> {
-        fetch_earnings_data(&self.db.pool, client).await
+    pub async fn get_aggregates(&self, dataset: &str, aggregate: &AggregateParams, params: &QueryParams) -> Result<Option<Page<Aggregate>>, Box<dyn std::error::Error>> {
+        self.db.find_aggregates(dataset, aggregate, params).await
     }
 
-    pub async fn fetch_and_store_runepools(&self, client: &reqwest::Client) -> Result<(), Box<dyn std::error::Error>> {
-        fetch_runepool_data(&self.db.pool, client).await
+    pub async fn get_candles(&self, pool_id: &str, candles: &CandleParams, params: &QueryParams) -> Result<Option<Page<Candle>>, Box<dyn std::error::Error>> {
+        self.db.find_candles(pool_id, candles, params).await
+    }
+
+    pub async fn get_yields(&self, pool_id: &str, yields: &YieldParams, params: &QueryParams) -> Result<Option<Vec<PoolYield>>, Box<dyn std::error::Error>> {
+        self.db.find_yields(pool_id, &yields.windows, params).await
+    }
+
+    pub async fn get_lp_return(&self, pool_id: &str, params: &LpReturnParams, exit: DateTime<Utc>) -> Result<Option<LpReturn>, Box<dyn std::error::Error>> {
+        self.db.find_lp_return(pool_id, params, exit).await
+    }
+
+    pub async fn pool_exists(&self, pool_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        self.db.pool_exists(pool_id).await
+    }
+
+    pub async fn get_ingestion_status(&self) -> Result<Vec<IngestionStatus>, Box<dyn std::error::Error>> {
+        self.db.find_ingestion_status().await
+    }
+
+    pub async fn get_unresolved_gaps(&self) -> Result<Vec<IntervalGap>, Box<dyn std::error::Error>> {
+        self.db.find_unresolved_gaps().await
+    }
+
+    pub async fn repair_gaps(&self, client: &dyn MidgardClient, interval: Interval) -> Result<(), Box<dyn std::error::Error>> {
+        repair_gaps(&self.db.pool, client, interval).await
+    }
+
+    pub async fn fetch_and_store_depths(&self, client: &dyn MidgardClient, interval: Interval) -> Result<(), Box<dyn std::error::Error>> {
+        fetch_depth_data(&self.db.pool, client, interval).await
+    }
+
+    pub async fn fetch_and_store_swaps(&self, client: &dyn MidgardClient, interval: Interval) -> Result<(), Box<dyn std::error::Error>> {
+        fetch_swaps_data(&self.db.pool, client, interval).await
+    }
+
+    pub async fn fetch_and_store_earnings(&self, client: &dyn MidgardClient, interval: Interval) -> Result<(), Box<dyn std::error::Error>> {
+        fetch_earnings_data(&self.db.pool, client, interval).await
+    }
+
+    pub async fn fetch_and_store_runepools(&self, client: &dyn MidgardClient, interval: Interval) -> Result<(), Box<dyn std::error::Error>> {
+        fetch_runepool_data(&self.db.pool, client, interval).await
+    }
+
+    pub async fn backfill_depths(&self, client: &dyn MidgardClient, interval: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+        backfill_depth_data(&self.db.pool, client, interval, start).await
+    }
+
+    pub async fn backfill_swaps(&self, client: &dyn MidgardClient, interval: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+        backfill_swaps_data(&self.db.pool, client, interval, start).await
+    }
+
+    pub async fn backfill_earnings(&self, client: &dyn MidgardClient, interval: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+        backfill_earnings_data(&self.db.pool, client, interval, start).await
+    }
+
+    pub async fn backfill_runepools(&self, client: &dyn MidgardClient, interval: Interval, start: Option<DateTime<Utc>>) -> Result<(), Box<dyn std::error::Error>> {
+        backfill_runepool_data(&self.db.pool, client, interval, start).await
     }
 }
\ No newline at end of file